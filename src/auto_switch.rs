@@ -0,0 +1,466 @@
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::display::{self, format_reset_time};
+use crate::meta::ProfileMeta;
+use crate::profile;
+use crate::tool::Tool;
+use crate::usage::{
+    ProfileUsageCache, UsageCache, UsageLine, prefetch_claude_usage, prefetch_codex_usage,
+};
+
+fn default_threshold() -> f64 {
+    90.0
+}
+
+/// Opt-in policy that moves a tool off its current profile when any usage
+/// window reaches `threshold` percent used.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AutoSwitchPolicy {
+    #[serde(default = "default_threshold")]
+    pub threshold: f64,
+}
+
+/// A switch chosen by the policy, with the window that triggered it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwitchDecision {
+    pub tool: Tool,
+    pub from: String,
+    pub to: String,
+    pub window: String,
+    pub percent: f64,
+    pub threshold: f64,
+}
+
+impl SwitchDecision {
+    pub fn reason(&self) -> String {
+        format!(
+            "{} usage of '{}' reached {:.1}% (threshold {}%)",
+            self.window, self.from, self.percent, self.threshold
+        )
+    }
+}
+
+/// The most used window of an entry.
+fn peak_usage(entry: &ProfileUsageCache) -> Option<(&str, f64)> {
+    entry
+        .usage_lines
+        .iter()
+        .filter_map(|line| match line {
+            UsageLine::Data { label, percent, .. } => Some((label.as_str(), *percent)),
+            UsageLine::Text(_) => None,
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// A profile's remaining quota, as used to order switch candidates.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Candidate {
+    pub profile: String,
+    /// Percent left in each window; `None` when the window was not reported.
+    pub weekly_left: Option<f64>,
+    pub five_hour_left: Option<f64>,
+    pub next_reset: Option<DateTime<Utc>>,
+}
+
+/// Why a profile cannot be ranked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Excluded {
+    Stale,
+    RateLimited,
+    NoData,
+}
+
+impl std::fmt::Display for Excluded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Excluded::Stale => write!(f, "stale usage data"),
+            Excluded::RateLimited => write!(f, "rate limited"),
+            Excluded::NoData => write!(f, "no usage data"),
+        }
+    }
+}
+
+fn candidate(
+    profile: &str,
+    entry: &ProfileUsageCache,
+    now: DateTime<Utc>,
+) -> Result<Candidate, Excluded> {
+    if entry.is_stale {
+        return Err(Excluded::Stale);
+    }
+    if entry.rate_limited_until.is_some_and(|until| until > now) {
+        return Err(Excluded::RateLimited);
+    }
+    let mut candidate = Candidate {
+        profile: profile.to_string(),
+        weekly_left: None,
+        five_hour_left: None,
+        next_reset: None,
+    };
+    let mut has_data = false;
+    for line in &entry.usage_lines {
+        if let UsageLine::Data {
+            label,
+            percent,
+            resets_at,
+        } = line
+        {
+            has_data = true;
+            let left = 100.0 - percent;
+            match label.as_str() {
+                "Weekly" => candidate.weekly_left = Some(left),
+                "5-hour" => candidate.five_hour_left = Some(left),
+                _ => {}
+            }
+            candidate.next_reset = match (candidate.next_reset, *resets_at) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            };
+        }
+    }
+    if has_data {
+        Ok(candidate)
+    } else {
+        Err(Excluded::NoData)
+    }
+}
+
+/// Orders profiles by weekly headroom, then 5-hour headroom, then soonest reset.
+///
+/// Stale, rate-limited and data-less profiles are returned separately.
+pub fn rank(cache: &UsageCache, now: DateTime<Utc>) -> (Vec<Candidate>, Vec<(String, Excluded)>) {
+    let mut ranked = Vec::new();
+    let mut excluded = Vec::new();
+    for (profile, entry) in cache {
+        match candidate(profile, entry, now) {
+            Ok(c) => ranked.push(c),
+            Err(reason) => excluded.push((profile.clone(), reason)),
+        }
+    }
+    ranked.sort_by(|a, b| {
+        by_headroom(a.weekly_left, b.weekly_left)
+            .then_with(|| by_headroom(a.five_hour_left, b.five_hour_left))
+            .then_with(|| match (a.next_reset, b.next_reset) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                (x, y) => x.is_none().cmp(&y.is_none()),
+            })
+            .then_with(|| a.profile.cmp(&b.profile))
+    });
+    excluded.sort_by(|a, b| a.0.cmp(&b.0));
+    (ranked, excluded)
+}
+
+/// Orders more headroom first, and unknown headroom after any known value.
+fn by_headroom(a: Option<f64>, b: Option<f64>) -> std::cmp::Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => b.total_cmp(&a),
+        (a, b) => a.is_none().cmp(&b.is_none()),
+    }
+}
+
+/// Picks the best-ranked profile when `current` is at or above the threshold.
+///
+/// Only candidates whose every window is below the threshold qualify.
+pub fn decide(
+    tool: Tool,
+    current: &str,
+    cache: &UsageCache,
+    threshold: f64,
+    now: DateTime<Utc>,
+) -> Option<SwitchDecision> {
+    let entry = cache.get(current).filter(|e| !e.is_stale)?;
+    let (window, percent) = peak_usage(entry)?;
+    if percent < threshold {
+        return None;
+    }
+
+    let (ranked, _) = rank(cache, now);
+    let to = ranked
+        .into_iter()
+        .filter(|c| c.profile != current)
+        .find(|c| {
+            cache
+                .get(&c.profile)
+                .and_then(peak_usage)
+                .is_some_and(|(_, peak)| peak < threshold)
+        })?;
+
+    Some(SwitchDecision {
+        tool,
+        from: current.to_string(),
+        to: to.profile,
+        window: window.to_string(),
+        percent,
+        threshold,
+    })
+}
+
+/// Applies `policy` to a freshly fetched cache, switching profiles if needed.
+///
+/// The reason is recorded in the new profile's `meta.json`.
+pub async fn run(
+    policy: &AutoSwitchPolicy,
+    tool: Tool,
+    cache: &UsageCache,
+) -> Result<Option<SwitchDecision>> {
+    let Some(current) = tool.current_profile()? else {
+        return Ok(None);
+    };
+    let Some(decision) = decide(tool, &current, cache, policy.threshold, Utc::now()) else {
+        return Ok(None);
+    };
+
+    profile::switch(tool, &decision.to).await?;
+    let reason = decision.reason();
+    let dir = tool.profile_dir(&decision.to)?;
+    let _ = tokio::task::spawn_blocking(move || {
+        let _lock = tool.lock_profiles()?;
+        ProfileMeta::update(&dir, |m| m.switch_reason = Some(reason))
+    })
+    .await;
+    Ok(Some(decision))
+}
+
+fn format_left(left: Option<f64>) -> String {
+    left.map(|left| format!("{:.1}%", left.clamp(0.0, 100.0)))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn format_ranking(ranked: &[Candidate], current: Option<&str>) -> Vec<String> {
+    let rows: Vec<Vec<String>> = ranked
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            vec![
+                (i + 1).to_string(),
+                c.profile.clone(),
+                if current == Some(c.profile.as_str()) {
+                    "*"
+                } else {
+                    ""
+                }
+                .to_string(),
+                format_left(c.weekly_left),
+                format_left(c.five_hour_left),
+                c.next_reset.map(format_reset_time).unwrap_or_default(),
+            ]
+        })
+        .collect();
+    display::format_table(
+        &[
+            "RANK",
+            "PROFILE",
+            "CURRENT",
+            "WEEKLY LEFT",
+            "5-HOUR LEFT",
+            "NEXT RESET",
+        ],
+        &rows,
+    )
+}
+
+/// Fetches usage for every profile of `tool`, prints the ranking and
+/// switches to the best one.
+pub async fn cmd_switch_best(tool: Tool) -> Result<()> {
+    let profiles = tool.list_profiles()?;
+    if profiles.is_empty() {
+        bail!("no profiles saved for {}", tool);
+    }
+    let cache = match tool {
+        Tool::Claude => prefetch_claude_usage(&profiles).await,
+        Tool::Codex => prefetch_codex_usage(&profiles).await,
+    };
+    let current = tool.current_profile()?;
+    let (ranked, excluded) = rank(&cache, Utc::now());
+
+    for line in format_ranking(&ranked, current.as_deref()) {
+        println!("{}", line);
+    }
+    for (profile, reason) in &excluded {
+        println!("Skipped '{}': {}", profile, reason);
+    }
+
+    let Some(best) = ranked.first() else {
+        bail!("no profile with fresh usage data is available for {}", tool);
+    };
+    if current.as_deref() == Some(best.profile.as_str()) {
+        println!("Profile '{}' is already active for {}", best.profile, tool);
+        return Ok(());
+    }
+    profile::switch(tool, &best.profile).await?;
+    println!("Switched to profile '{}' for {}", best.profile, tool);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(percents: &[f64]) -> ProfileUsageCache {
+        ProfileUsageCache {
+            usage_lines: percents
+                .iter()
+                .zip(["5-hour", "Weekly"])
+                .map(|(percent, label)| UsageLine::Data {
+                    label: label.to_string(),
+                    percent: *percent,
+                    resets_at: None,
+                })
+                .collect(),
+            plan_type: None,
+            is_stale: false,
+            rate_limited_until: None,
+        }
+    }
+
+    fn cache(entries: Vec<(&str, ProfileUsageCache)>) -> UsageCache {
+        entries
+            .into_iter()
+            .map(|(name, e)| (name.to_string(), e))
+            .collect()
+    }
+
+    #[test]
+    fn stays_below_threshold() {
+        let cache = cache(vec![("a", entry(&[50.0, 89.9])), ("b", entry(&[0.0, 0.0]))]);
+
+        assert_eq!(decide(Tool::Claude, "a", &cache, 90.0, Utc::now()), None);
+    }
+
+    #[test]
+    fn switches_to_profile_with_most_headroom() {
+        let cache = cache(vec![
+            ("a", entry(&[20.0, 95.0])),
+            ("b", entry(&[10.0, 60.0])),
+            ("c", entry(&[30.0, 40.0])),
+        ]);
+
+        let decision = decide(Tool::Claude, "a", &cache, 90.0, Utc::now()).unwrap();
+
+        assert_eq!(decision.to, "c");
+        assert_eq!(decision.window, "Weekly");
+        assert_eq!(
+            decision.reason(),
+            "Weekly usage of 'a' reached 95.0% (threshold 90%)"
+        );
+    }
+
+    #[test]
+    fn skips_stale_rate_limited_and_exhausted_candidates() {
+        let now = Utc::now();
+        let mut stale = entry(&[0.0, 0.0]);
+        stale.is_stale = true;
+        let mut limited = entry(&[0.0, 0.0]);
+        limited.rate_limited_until = Some(now + chrono::TimeDelta::minutes(5));
+        let cache = cache(vec![
+            ("a", entry(&[92.0, 50.0])),
+            ("stale", stale),
+            ("limited", limited),
+            ("full", entry(&[95.0, 10.0])),
+        ]);
+
+        assert_eq!(decide(Tool::Codex, "a", &cache, 90.0, now), None);
+    }
+
+    #[test]
+    fn ignores_stale_current_profile() {
+        let mut current = entry(&[99.0, 99.0]);
+        current.is_stale = true;
+        let cache = cache(vec![("a", current), ("b", entry(&[0.0, 0.0]))]);
+
+        assert_eq!(decide(Tool::Claude, "a", &cache, 90.0, Utc::now()), None);
+    }
+
+    fn with_reset(mut entry: ProfileUsageCache, resets_at: &str) -> ProfileUsageCache {
+        for line in &mut entry.usage_lines {
+            if let UsageLine::Data { resets_at: r, .. } = line {
+                *r = Some(resets_at.parse().unwrap());
+            }
+        }
+        entry
+    }
+
+    fn ranked_names(cache: &UsageCache) -> Vec<String> {
+        rank(cache, Utc::now())
+            .0
+            .into_iter()
+            .map(|c| c.profile)
+            .collect()
+    }
+
+    #[test]
+    fn rank_prefers_weekly_then_five_hour_headroom() {
+        let cache = cache(vec![
+            ("a", entry(&[10.0, 50.0])),
+            ("b", entry(&[40.0, 20.0])),
+            ("c", entry(&[5.0, 20.0])),
+        ]);
+
+        assert_eq!(ranked_names(&cache), vec!["c", "b", "a"]);
+    }
+
+    #[test]
+    fn rank_puts_profiles_missing_a_window_after_known_ones() {
+        let cache = cache(vec![
+            ("no-weekly", entry(&[0.0])),
+            ("busy", entry(&[80.0, 90.0])),
+        ]);
+
+        assert_eq!(ranked_names(&cache), vec!["busy", "no-weekly"]);
+        assert_eq!(rank(&cache, Utc::now()).0[1].weekly_left, None);
+    }
+
+    #[test]
+    fn rank_breaks_ties_by_soonest_reset() {
+        let cache = cache(vec![
+            (
+                "late",
+                with_reset(entry(&[10.0, 10.0]), "2026-01-02T00:00:00Z"),
+            ),
+            (
+                "soon",
+                with_reset(entry(&[10.0, 10.0]), "2026-01-01T00:00:00Z"),
+            ),
+            ("unknown", entry(&[10.0, 10.0])),
+        ]);
+
+        assert_eq!(ranked_names(&cache), vec!["soon", "late", "unknown"]);
+    }
+
+    #[test]
+    fn rank_excludes_stale_rate_limited_and_empty_profiles() {
+        let now = Utc::now();
+        let mut stale = entry(&[0.0, 0.0]);
+        stale.is_stale = true;
+        let mut limited = entry(&[0.0, 0.0]);
+        limited.rate_limited_until = Some(now + chrono::TimeDelta::minutes(5));
+        let cache = cache(vec![
+            ("ok", entry(&[50.0, 50.0])),
+            ("stale", stale),
+            ("limited", limited),
+            ("empty", entry(&[])),
+        ]);
+
+        let (ranked, excluded) = rank(&cache, now);
+
+        assert_eq!(ranked.len(), 1);
+        assert_eq!(ranked[0].profile, "ok");
+        assert_eq!(
+            excluded,
+            vec![
+                ("empty".to_string(), Excluded::NoData),
+                ("limited".to_string(), Excluded::RateLimited),
+                ("stale".to_string(), Excluded::Stale),
+            ]
+        );
+    }
+
+    #[test]
+    fn policy_threshold_defaults_to_ninety() {
+        let policy: AutoSwitchPolicy = serde_json::from_str("{}").unwrap();
+
+        assert_eq!(policy.threshold, 90.0);
+    }
+}
@@ -0,0 +1,176 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Local, NaiveDateTime, TimeDelta, Utc};
+
+use crate::config::Config;
+use crate::display;
+use crate::fs_util::atomic_write_private;
+
+const BACKUP_DIR: &str = "backups";
+const STAMP_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+
+/// A previous version of a profile's credentials, kept in `<profile>/backups`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub taken_at: DateTime<Utc>,
+}
+
+fn backup_dir(profile_dir: &Path) -> PathBuf {
+    profile_dir.join(BACKUP_DIR)
+}
+
+/// Backups of a profile, newest first.
+pub fn versions(profile_dir: &Path) -> Result<Vec<Backup>> {
+    let dir = backup_dir(profile_dir);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut backups: Vec<Backup> = fs::read_dir(&dir)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let stamp = path.file_name()?.to_str()?.strip_suffix(".json")?;
+            let taken_at = NaiveDateTime::parse_from_str(stamp, STAMP_FORMAT)
+                .ok()?
+                .and_utc();
+            Some(Backup { path, taken_at })
+        })
+        .collect();
+    backups.sort_by_key(|b| std::cmp::Reverse(b.taken_at));
+    Ok(backups)
+}
+
+fn record_with(profile_dir: &Path, content: &str, keep: usize, now: DateTime<Utc>) -> Result<()> {
+    if keep == 0 {
+        return Ok(());
+    }
+    let dir = backup_dir(profile_dir);
+    fs::create_dir_all(&dir)?;
+    // Never replace a backup taken within the same millisecond.
+    let mut taken_at = now;
+    let path = loop {
+        let path = dir.join(format!("{}.json", taken_at.format(STAMP_FORMAT)));
+        if !path.exists() {
+            break path;
+        }
+        taken_at += TimeDelta::milliseconds(1);
+    };
+    atomic_write_private(&path, content)?;
+    for old in versions(profile_dir)?.into_iter().skip(keep) {
+        fs::remove_file(old.path)?;
+    }
+    Ok(())
+}
+
+/// Saves `content` as the newest backup of the profile, dropping versions
+/// beyond the configured limit.
+pub fn record(profile_dir: &Path, content: &str) -> Result<()> {
+    record_with(
+        profile_dir,
+        content,
+        Config::load().credential_backups(),
+        Utc::now(),
+    )
+}
+
+/// Reads backup `version`, where 1 is the most recent.
+pub fn read_version(profile_dir: &Path, version: usize) -> Result<String> {
+    let backups = versions(profile_dir)?;
+    let backup = version
+        .checked_sub(1)
+        .and_then(|i| backups.get(i))
+        .ok_or_else(|| match backups.len() {
+            0 => anyhow!("no credential backups for this profile"),
+            n => anyhow!("backup version {} not found (1-{} available)", version, n),
+        })?;
+    Ok(fs::read_to_string(&backup.path)?)
+}
+
+pub fn format_table(backups: &[Backup]) -> Vec<String> {
+    let rows: Vec<Vec<String>> = backups
+        .iter()
+        .enumerate()
+        .map(|(i, backup)| {
+            vec![
+                (i + 1).to_string(),
+                backup
+                    .taken_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string(),
+            ]
+        })
+        .collect();
+    display::format_table(&["VERSION", "TAKEN"], &rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn record_keeps_the_newest_versions() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for (i, time) in [
+            "2026-01-01T00:00:00Z",
+            "2026-01-02T00:00:00Z",
+            "2026-01-03T00:00:00Z",
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            record_with(dir.path(), &format!("v{}", i), 2, at(time)).unwrap();
+        }
+
+        let backups = versions(dir.path()).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(backups[0].taken_at, at("2026-01-03T00:00:00Z"));
+        assert_eq!(read_version(dir.path(), 1).unwrap(), "v2");
+        assert_eq!(read_version(dir.path(), 2).unwrap(), "v1");
+        assert!(read_version(dir.path(), 3).is_err());
+        assert!(read_version(dir.path(), 0).is_err());
+    }
+
+    #[test]
+    fn record_does_not_replace_backup_from_the_same_instant() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = at("2026-01-01T00:00:00Z");
+
+        record_with(dir.path(), "first", 5, now).unwrap();
+        record_with(dir.path(), "second", 5, now).unwrap();
+
+        assert_eq!(read_version(dir.path(), 1).unwrap(), "second");
+        assert_eq!(read_version(dir.path(), 2).unwrap(), "first");
+    }
+
+    #[test]
+    fn record_with_zero_keep_writes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+
+        record_with(dir.path(), "v0", 0, Utc::now()).unwrap();
+
+        assert!(!backup_dir(dir.path()).exists());
+        let err = read_version(dir.path(), 1).unwrap_err();
+        assert!(err.to_string().contains("no credential backups"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn backups_are_owner_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        record_with(dir.path(), "secret", 1, Utc::now()).unwrap();
+
+        let backup = &versions(dir.path()).unwrap()[0];
+        let mode = fs::metadata(&backup.path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o600);
+    }
+}
@@ -1,340 +1,417 @@
-use std::fs;
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
-use std::process::Command;
+use std::path::{Path, PathBuf};
 
-use anyhow::{Result, anyhow};
+use anyhow::Result;
+use sha2::{Digest, Sha256};
 
-use crate::fs_util;
+use super::usage;
+use crate::credential_store::{CredentialStore, FileStore, KeychainStore};
+use crate::meta::ProfileMeta;
+use crate::profile::{ProfileManager, Recovery};
 use crate::tool::Tool;
 
 const TOOL: Tool = Tool::Claude;
 const KEYCHAIN_SERVICE: &str = "Claude Code-credentials";
+const CREDENTIALS_FILE: &str = ".credentials.json";
+const PROFILE_CREDENTIALS_FILE: &str = "credentials.json";
 
-/// Decode hex-encoded credentials returned by `security -w` for blob entries.
-///
-/// Claude Code stores credentials as a binary blob in Keychain.
-/// `security find-generic-password -w` returns blob data as a hex string
-/// (e.g. "7b0a2022..." for '{\n "...'), which must be decoded back to JSON.
-fn decode_hex_credentials(data: &str) -> String {
-    if data.starts_with('{') {
-        return data.to_string();
-    }
-    if !data.len().is_multiple_of(2) || !data.bytes().all(|b| b.is_ascii_hexdigit()) {
-        return data.to_string();
-    }
-    let bytes: Vec<u8> = (0..data.len())
-        .step_by(2)
-        .filter_map(|i| u8::from_str_radix(&data[i..i + 2], 16).ok())
-        .collect();
-    match String::from_utf8(bytes) {
-        Ok(s) if s.starts_with('{') => s,
-        _ => data.to_string(),
-    }
+/// Claude Code keeps its credentials in the Keychain on macOS and in
+/// `~/.claude/.credentials.json` everywhere else.
+fn uses_keychain() -> bool {
+    cfg!(target_os = "macos")
 }
 
-fn read_keychain() -> Result<String> {
-    let output = Command::new("security")
-        .args(["find-generic-password", "-s", KEYCHAIN_SERVICE, "-w"])
-        .output()?;
-    if !output.status.success() {
-        return Err(anyhow!(
-            "failed to read credentials from Keychain (service: {})",
-            KEYCHAIN_SERVICE
-        ));
-    }
-    let data = String::from_utf8(output.stdout)?;
-    let trimmed = data.trim_end_matches('\n');
-    if trimmed.is_empty() {
-        return Err(anyhow!("Keychain entry is empty"));
+fn store() -> Result<Box<dyn CredentialStore + Send>> {
+    if uses_keychain() {
+        Ok(Box::new(KeychainStore::new(
+            KEYCHAIN_SERVICE,
+            PROFILE_CREDENTIALS_FILE,
+        )))
+    } else {
+        Ok(Box::new(FileStore::new(
+            TOOL.home_dir()?.join(CREDENTIALS_FILE),
+            PROFILE_CREDENTIALS_FILE,
+        )))
     }
-    Ok(decode_hex_credentials(trimmed))
 }
 
-fn encode_hex(data: &str) -> String {
-    data.bytes().map(|b| format!("{:02x}", b)).collect()
+fn isolated_store(config_dir: &Path) -> Box<dyn CredentialStore + Send> {
+    Box::new(FileStore::new(
+        config_dir.join(CREDENTIALS_FILE),
+        PROFILE_CREDENTIALS_FILE,
+    ))
 }
 
-fn write_keychain(data: &str) -> Result<()> {
-    let account = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
-    let hex_data = encode_hex(data);
-
-    // Store as hex blob (-X) to match Claude Code's format.
-    // Claude Code reads Keychain with `security -w` which returns hex for blob
-    // entries, then hex-decodes before JSON parsing.
-    // -U updates an existing entry or creates a new one atomically.
-    let output = Command::new("security")
-        .args([
-            "add-generic-password",
-            "-U",
-            "-s",
-            KEYCHAIN_SERVICE,
-            "-a",
-            &account,
-            "-X",
-            &hex_data,
-        ])
-        .output()?;
-    if !output.status.success() {
-        return Err(anyhow!(
-            "failed to write credentials to Keychain (service: {})",
-            KEYCHAIN_SERVICE
-        ));
-    }
-    Ok(())
+/// SHA-256 fingerprint of an access token, kept in metadata instead of the token.
+fn token_hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
 }
 
-pub fn switch(profile: &str) -> Result<()> {
-    let profile_dir = TOOL.profile_dir(profile)?;
-    if !profile_dir.exists() {
-        return Err(anyhow!("profile '{}' does not exist for {}", profile, TOOL));
+/// Looks up the account behind the active credentials. The account is `None`
+/// when it cannot be determined (no credentials, expired token, network error).
+///
+/// When `profile_dir`'s metadata already confirms the token, no request is
+/// made; otherwise a confirmed token is remembered there.
+async fn resolve_active_account(
+    store: Box<dyn CredentialStore + Send>,
+    profile_dir: Option<PathBuf>,
+) -> Result<(Box<dyn CredentialStore + Send>, Option<String>)> {
+    let (store, data) = tokio::task::spawn_blocking(move || {
+        let data = store.read_active().ok().flatten();
+        (store, data)
+    })
+    .await?;
+    let Some(token) = data.as_deref().and_then(usage::access_token) else {
+        return Ok((store, None));
+    };
+    let hash = token_hash(&token);
+    let meta = profile_dir
+        .as_deref()
+        .map(ProfileMeta::load)
+        .unwrap_or_default();
+    if meta.account_id.is_some() && meta.account_token.as_deref() == Some(hash.as_str()) {
+        return Ok((store, meta.account_id));
     }
-
-    // Save current Keychain credentials to current profile
-    sync_keychain_to_current_profile();
-
-    // Load new profile's credentials
-    let src = profile_dir.join("credentials.json");
-    if !src.exists() {
-        return Err(anyhow!(
-            "credentials file not found for profile '{}' ({})",
-            profile,
-            TOOL
-        ));
+    let account = usage::fetch_account(&token)
+        .await
+        .ok()
+        .map(|p| p.account.uuid);
+    if let Some(dir) = profile_dir
+        && account.is_some()
+        && account == meta.account_id
+    {
+        let _ = tokio::task::spawn_blocking(move || {
+            let _lock = TOOL.lock_profiles()?;
+            ProfileMeta::update(&dir, |m| m.account_token = Some(hash))
+        })
+        .await;
     }
-    let raw = fs::read_to_string(&src)?;
-    let data = decode_hex_credentials(&raw);
-    // Persist decoded credentials back to file if hex was decoded
-    if data != raw {
-        if let Err(e) = fs_util::atomic_write(&src, &data) {
-            eprintln!("warning: failed to update credentials format: {e}");
+    Ok((store, account))
+}
+
+/// Refuses to overwrite a profile that was recorded for a different account.
+///
+/// Only a definite mismatch blocks the sync; when either side is unknown the
+/// sync goes ahead.
+fn accounts_match(profile: &str, active: Option<&str>, stored: Option<&str>) -> bool {
+    match (active, stored) {
+        (Some(active), Some(stored)) if active != stored => {
+            eprintln!(
+                "Warning: Active Claude credentials (account: '{}') differ from profile '{}' (account: '{}').",
+                active, profile, stored,
+            );
+            eprintln!("Skipping sync to protect stored credentials.");
+            eprintln!("Re-authenticate and run 'aip save' to save to the correct profile.");
+            false
         }
-        #[cfg(unix)]
-        if let Err(e) = fs::set_permissions(&src, fs::Permissions::from_mode(0o600)) {
-            eprintln!("warning: failed to set credentials file permissions: {e}");
+        (Some(active), None) => {
+            eprintln!(
+                "Warning: Profile '{}' has no recorded account. Syncing anyway and recording '{}'.",
+                profile, active,
+            );
+            true
         }
+        _ => true,
     }
+}
 
-    // Update _current first, then write credentials to Keychain.
-    // If Keychain write fails, roll back _current to avoid contamination.
-    let current_file = TOOL.current_file()?;
-    let old_current = fs::read_to_string(&current_file).ok();
-    fs_util::atomic_write(&current_file, &format!("{}\n", profile))?;
-
-    if let Err(e) = write_keychain(&data) {
-        // Roll back _current to previous value
-        match &old_current {
-            Some(prev) => {
-                let _ = fs_util::atomic_write(&current_file, prev);
-            }
-            None => {
-                let _ = fs::remove_file(&current_file);
-            }
+/// Sync guard comparing the active account with the one recorded in the
+/// profile's metadata under `profiles_dir`, recording it when the profile has
+/// none yet.
+fn account_guard(
+    profiles_dir: PathBuf,
+    active: Option<String>,
+) -> impl Fn(&str, &str, Option<&str>) -> bool {
+    move |profile, _, _| {
+        let dir = profiles_dir.join(profile);
+        let meta = ProfileMeta::load(&dir);
+        if !accounts_match(profile, active.as_deref(), meta.account_id.as_deref()) {
+            return false;
+        }
+        if meta.account_id.is_none() && active.is_some() {
+            let _ = ProfileMeta::update(&dir, |m| m.account_id = active.clone());
         }
-        return Err(e);
+        true
     }
+}
 
-    Ok(())
+/// Builds a manager whose syncs into `profile` are checked against the active
+/// account. Resolving the accounts needs the network, so it happens up front:
+/// a profile without a recorded account gets it from its own stored token.
+async fn checked_manager(
+    store: Box<dyn CredentialStore + Send>,
+    profile: Option<&str>,
+) -> Result<ProfileManager> {
+    if let Some(profile) = profile {
+        record_account(profile, false).await;
+    }
+    let profile_dir = profile.and_then(|p| TOOL.profile_dir(p).ok());
+    let (store, account) = resolve_active_account(store, profile_dir).await?;
+    let profiles_dir = TOOL.profiles_dir()?;
+    Ok(ProfileManager::new(TOOL, profiles_dir.clone(), store)
+        .with_sync_guard(account_guard(profiles_dir, account)))
+}
+
+/// Manager whose active credentials live in an isolated `CLAUDE_CONFIG_DIR`.
+pub fn isolated_manager(config_dir: &Path) -> Result<ProfileManager> {
+    ProfileManager::for_tool(TOOL, isolated_store(config_dir))
 }
 
-pub fn sync_keychain_to_current_profile() {
-    let current = match TOOL.current_profile() {
-        Ok(Some(name)) => name,
-        _ => return,
+/// Syncs credentials refreshed inside an isolated `CLAUDE_CONFIG_DIR` back
+/// into `profile`, unless the profile changed since `activated` was copied in.
+pub async fn sync_isolated_to_profile(config_dir: &Path, profile: &str, activated: String) {
+    let Ok(manager) = checked_manager(isolated_store(config_dir), Some(profile)).await else {
+        return;
     };
-    let dest = match TOOL.profile_dir(&current) {
-        Ok(dir) => dir.join("credentials.json"),
-        _ => return,
+    let profile = profile.to_string();
+    let _ = tokio::task::spawn_blocking(move || {
+        let _lock = TOOL.lock_profiles()?;
+        manager.sync_active_to_unchanged_profile(&profile, &activated);
+        Ok::<(), anyhow::Error>(())
+    })
+    .await;
+}
+
+/// Records the account behind `profile`'s stored credentials (id, email and
+/// organization) in its metadata, so later syncs can tell when someone logged
+/// in as another account.
+async fn record_account(profile: &str, replace: bool) {
+    let Ok(dir) = TOOL.profile_dir(profile) else {
+        return;
+    };
+    if ProfileMeta::load(&dir).account_id.is_some() && !replace {
+        return;
+    }
+    let Ok(content) = tokio::fs::read_to_string(dir.join(PROFILE_CREDENTIALS_FILE)).await else {
+        return;
     };
-    let data = match read_keychain() {
-        Ok(d) => d,
-        Err(_) => return,
+    let Some(token) = usage::access_token(&content) else {
+        return;
     };
-    if let Err(e) = fs_util::atomic_write(&dest, &data) {
-        eprintln!(
-            "Warning: failed to sync credentials to profile '{}': {}",
-            current, e
-        );
+    let Ok(profile) = usage::fetch_account(&token).await else {
         return;
+    };
+    let _ = tokio::task::spawn_blocking(move || {
+        let _lock = TOOL.lock_profiles()?;
+        ProfileMeta::update(&dir, |m| {
+            if m.account_id.as_deref() != Some(profile.account.uuid.as_str()) {
+                m.account_token = None;
+            }
+            m.account_id = Some(profile.account.uuid);
+            m.email = profile.account.email;
+            m.organization = profile.organization.and_then(|o| o.name);
+        })
+    })
+    .await;
+}
+
+pub async fn switch(profile: &str) -> Result<()> {
+    let current = TOOL.current_profile()?;
+    let manager = checked_manager(store()?, current.as_deref()).await?;
+    let name = profile.to_string();
+    tokio::task::spawn_blocking(move || {
+        let _lock = TOOL.lock_profiles()?;
+        manager.switch(&name)
+    })
+    .await??;
+    record_account(profile, false).await;
+    Ok(())
+}
+
+pub async fn sync_keychain_to_current_profile() {
+    let Ok(store) = store() else {
+        return;
+    };
+    let Ok(Some(current)) = TOOL.current_profile() else {
+        return;
+    };
+    let Ok(manager) = checked_manager(store, Some(&current)).await else {
+        return;
+    };
+    let _ = tokio::task::spawn_blocking(move || {
+        let _lock = TOOL.lock_profiles()?;
+        manager.sync_active_to_current_profile();
+        Ok::<(), anyhow::Error>(())
+    })
+    .await;
+}
+
+pub async fn save(name: &str) -> Result<()> {
+    let manager = ProfileManager::for_tool(TOOL, store()?)?;
+    let profile = name.to_string();
+    tokio::task::spawn_blocking(move || {
+        let _lock = TOOL.lock_profiles()?;
+        manager.save(&profile)
+    })
+    .await??;
+    record_account(name, true).await;
+    Ok(())
+}
+
+pub fn restore(profile: &str, version: usize) -> Result<()> {
+    let manager = ProfileManager::for_tool(TOOL, store()?)?;
+    let _lock = TOOL.lock_profiles()?;
+    manager.restore(profile, version)
+}
+
+/// Finishes or rolls back a switch interrupted by a killed aip.
+pub fn recover_switch() -> Result<Option<Recovery>> {
+    let manager = ProfileManager::for_tool(TOOL, store()?)?;
+    if !manager.has_pending_switch() {
+        return Ok(None);
     }
-    #[cfg(unix)]
-    let _ = fs::set_permissions(&dest, fs::Permissions::from_mode(0o600));
+    let _lock = TOOL.lock_profiles()?;
+    manager.recover()
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
     use super::*;
+    use crate::credential_store::MemoryStore;
 
-    /// Simulate the switch rollback logic: if the operation after _current
-    /// update fails, _current must be restored to its previous value.
-    #[test]
-    fn switch_rolls_back_current_on_credential_failure() {
+    fn setup(store: MemoryStore) -> (tempfile::TempDir, MemoryStore, ProfileManager) {
         let dir = tempfile::tempdir().unwrap();
-        let current_file = dir.path().join("_current");
-
-        // Pre-existing _current value
-        fs::write(&current_file, "old-profile\n").unwrap();
-
-        let old_current = fs::read_to_string(&current_file).ok();
-        fs_util::atomic_write(&current_file, "new-profile\n").unwrap();
-        assert_eq!(fs::read_to_string(&current_file).unwrap(), "new-profile\n");
-
-        // Simulate credential write failure -> rollback
-        let credential_write_failed = true;
-        if credential_write_failed {
-            match &old_current {
-                Some(prev) => {
-                    let _ = fs_util::atomic_write(&current_file, prev);
-                }
-                None => {
-                    let _ = fs::remove_file(&current_file);
-                }
-            }
-        }
+        let manager = ProfileManager::new(TOOL, dir.path().to_path_buf(), Box::new(store.clone()));
+        (dir, store, manager)
+    }
 
-        assert_eq!(fs::read_to_string(&current_file).unwrap(), "old-profile\n");
+    fn add_profile(dir: &Path, store: &MemoryStore, name: &str, data: &str) {
+        let profile_dir = dir.join(name);
+        fs::create_dir_all(&profile_dir).unwrap();
+        store.write_profile(&profile_dir, data).unwrap();
     }
 
-    /// When _current didn't exist before switch, rollback should remove it.
     #[test]
-    fn switch_removes_current_on_rollback_when_no_previous() {
-        let dir = tempfile::tempdir().unwrap();
-        let current_file = dir.path().join("_current");
-
-        let old_current: Option<String> = fs::read_to_string(&current_file).ok();
-        assert!(old_current.is_none());
-
-        fs_util::atomic_write(&current_file, "new-profile\n").unwrap();
-        assert!(current_file.exists());
-
-        // Simulate failure -> rollback
-        match &old_current {
-            Some(prev) => {
-                let _ = fs_util::atomic_write(&current_file, prev);
-            }
-            None => {
-                let _ = fs::remove_file(&current_file);
-            }
-        }
+    fn accounts_match_blocks_only_definite_mismatch() {
+        assert!(!accounts_match("work", Some("acct-a"), Some("acct-b")));
+        assert!(accounts_match("work", Some("acct-a"), Some("acct-a")));
+        assert!(accounts_match("work", Some("acct-a"), None));
+        assert!(accounts_match("work", None, Some("acct-b")));
+        assert!(accounts_match("work", None, None));
+    }
 
-        assert!(!current_file.exists());
+    #[test]
+    fn token_hash_is_stable_sha256() {
+        assert_eq!(
+            token_hash("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
     }
 
-    /// Simulate save cleanup: newly created profile dir is removed on failure.
     #[test]
-    fn save_cleans_up_newly_created_dir_on_failure() {
+    fn resolve_active_account_uses_token_confirmed_in_meta() {
         let dir = tempfile::tempdir().unwrap();
-        let dest_dir = dir.path().join("profiles").join("new-profile");
-        let newly_created = !dest_dir.exists();
-        assert!(newly_created);
-
-        fs::create_dir_all(&dest_dir).unwrap();
-        assert!(dest_dir.exists());
+        let token = "sk-ant-oat-confirmed";
+        let active = format!(r#"{{"claudeAiOauth":{{"accessToken":"{}"}}}}"#, token);
+        ProfileMeta::update(dir.path(), |m| {
+            m.account_id = Some("acct-a".to_string());
+            m.account_token = Some(token_hash(token));
+        })
+        .unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+
+        let (_, account) = rt
+            .block_on(resolve_active_account(
+                Box::new(MemoryStore::with_active(&active)),
+                Some(dir.path().to_path_buf()),
+            ))
+            .unwrap();
+
+        assert_eq!(account.as_deref(), Some("acct-a"));
+    }
 
-        // Simulate failure in the inner closure
-        let result: Result<()> = Err(anyhow!("simulated write failure"));
+    #[test]
+    fn account_guard_blocks_sync_into_profile_of_another_account() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("token-a"));
+        let manager = manager.with_sync_guard(account_guard(
+            dir.path().to_path_buf(),
+            Some("acct-a".to_string()),
+        ));
+        add_profile(dir.path(), &store, "work", "token-b");
+        let work = dir.path().join("work");
 
-        if result.is_err() && newly_created {
-            let _ = fs::remove_dir_all(&dest_dir);
-        }
+        // The stored token's account, as looked up by `record_account`.
+        ProfileMeta::update(&work, |m| m.account_id = Some("acct-b".to_string())).unwrap();
+        manager.sync_active_to_profile("work");
+        assert_eq!(store.profile(&work).as_deref(), Some("token-b"));
 
-        assert!(!dest_dir.exists());
+        ProfileMeta::update(&work, |m| m.account_id = Some("acct-a".to_string())).unwrap();
+        manager.sync_active_to_profile("work");
+        assert_eq!(store.profile(&work).as_deref(), Some("token-a"));
     }
 
-    /// When save overwrites an existing profile, dir should NOT be removed on failure.
     #[test]
-    fn save_preserves_existing_dir_on_failure() {
-        let dir = tempfile::tempdir().unwrap();
-        let dest_dir = dir.path().join("profiles").join("existing-profile");
-        fs::create_dir_all(&dest_dir).unwrap();
-        fs::write(dest_dir.join("credentials.json"), "old-creds").unwrap();
-
-        let newly_created = !dest_dir.exists();
-        assert!(!newly_created);
+    fn account_guard_syncs_and_records_account_when_profile_has_none() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("token-a"));
+        let manager = manager.with_sync_guard(account_guard(
+            dir.path().to_path_buf(),
+            Some("acct-a".to_string()),
+        ));
+        add_profile(dir.path(), &store, "work", "expired-token");
+        let work = dir.path().join("work");
 
-        // Simulate failure
-        let result: Result<()> = Err(anyhow!("simulated write failure"));
+        manager.sync_active_to_profile("work");
 
-        if result.is_err() && newly_created {
-            let _ = fs::remove_dir_all(&dest_dir);
-        }
-
-        // Directory and contents preserved
-        assert!(dest_dir.exists());
+        assert_eq!(store.profile(&work).as_deref(), Some("token-a"));
         assert_eq!(
-            fs::read_to_string(dest_dir.join("credentials.json")).unwrap(),
-            "old-creds"
+            ProfileMeta::load(&work).account_id.as_deref(),
+            Some("acct-a")
         );
     }
 
     #[test]
-    fn decode_hex_credentials_passes_through_json() {
-        let json = r#"{"claudeAiOauth":{"accessToken":"abc"}}"#;
-        assert_eq!(decode_hex_credentials(json), json);
-    }
+    fn switch_rolls_back_current_on_credential_failure() {
+        let (dir, store, manager) =
+            setup(MemoryStore::with_active("creds-old").failing_active_writes());
+        add_profile(dir.path(), &store, "old-profile", "creds-old");
+        add_profile(dir.path(), &store, "new-profile", "creds-new");
+        fs::write(dir.path().join("_current"), "old-profile\n").unwrap();
 
-    #[test]
-    fn decode_hex_credentials_decodes_hex_encoded_json() {
-        let json = r#"{"key":"value"}"#;
-        let hex: String = json.bytes().map(|b| format!("{:02x}", b)).collect();
-        assert_eq!(decode_hex_credentials(&hex), json);
-    }
+        assert!(manager.switch("new-profile").is_err());
 
-    #[test]
-    fn decode_hex_credentials_passes_through_non_hex() {
-        let data = "not-hex-data!@#";
-        assert_eq!(decode_hex_credentials(data), data);
+        assert_eq!(
+            fs::read_to_string(dir.path().join("_current")).unwrap(),
+            "old-profile\n"
+        );
+        assert_eq!(store.active().as_deref(), Some("creds-old"));
     }
 
     #[test]
-    fn decode_hex_credentials_passes_through_odd_length_hex() {
-        let data = "7b0";
-        assert_eq!(decode_hex_credentials(data), data);
-    }
+    fn switch_removes_current_on_rollback_when_no_previous() {
+        let (dir, store, manager) = setup(MemoryStore::default().failing_active_writes());
+        add_profile(dir.path(), &store, "new-profile", "creds-new");
 
-    #[test]
-    fn decode_hex_credentials_passes_through_hex_that_is_not_json() {
-        // Hex that decodes to non-JSON
-        let data = "48454c4c4f"; // "HELLO"
-        assert_eq!(decode_hex_credentials(data), data);
-    }
+        assert!(manager.switch("new-profile").is_err());
 
-    #[test]
-    fn encode_hex_round_trips_with_decode() {
-        let json = r#"{"claudeAiOauth":{"accessToken":"abc"}}"#;
-        let hex = encode_hex(json);
-        assert_eq!(decode_hex_credentials(&hex), json);
+        assert!(!dir.path().join("_current").exists());
     }
 
     #[test]
-    fn encode_hex_produces_lowercase_hex() {
-        assert_eq!(encode_hex("AB"), "4142");
-    }
-}
-
-pub fn save(name: &str) -> Result<()> {
-    let data = read_keychain()?;
+    fn save_cleans_up_newly_created_dir_on_failure() {
+        let (dir, _store, manager) = setup(MemoryStore::default());
 
-    let dest_dir = TOOL.profile_dir(name)?;
-    let newly_created = !dest_dir.exists();
-    fs::create_dir_all(&dest_dir)?;
+        assert!(manager.save("new-profile").is_err());
 
-    let result = (|| -> Result<()> {
-        let creds_path = dest_dir.join("credentials.json");
-        fs_util::atomic_write(&creds_path, &data)?;
-        #[cfg(unix)]
-        fs::set_permissions(&creds_path, fs::Permissions::from_mode(0o600))?;
+        assert!(!dir.path().join("new-profile").exists());
+    }
 
-        // Update current profile to the newly saved one
-        let current_file = TOOL.current_file()?;
-        fs_util::atomic_write(&current_file, &format!("{}\n", name))?;
+    #[test]
+    fn save_preserves_existing_dir_on_failure() {
+        let (dir, store, manager) = setup(MemoryStore::default());
+        add_profile(dir.path(), &store, "existing-profile", "old-creds");
 
-        Ok(())
-    })();
+        assert!(manager.save("existing-profile").is_err());
 
-    if result.is_err() && newly_created {
-        let _ = fs::remove_dir_all(&dest_dir);
+        assert!(dir.path().join("existing-profile").exists());
+        assert_eq!(
+            store
+                .profile(&dir.path().join("existing-profile"))
+                .as_deref(),
+            Some("old-creds")
+        );
     }
-
-    result
 }
@@ -1,7 +1,5 @@
 use std::collections::HashMap;
 use std::fs;
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 use std::time::Duration;
 
@@ -10,9 +8,9 @@ use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use serde_json::Value;
 
-use crate::fs_util;
 use crate::http::shared_client;
 use crate::tool::Tool;
+use crate::{backup, fs_util};
 
 const CLIENT_ID: &str = "9d1c250a-e61b-44d9-88ed-5944d1962f5e";
 const TOKEN_URL: &str = "https://platform.claude.com/v1/oauth/token";
@@ -70,6 +68,25 @@ impl std::fmt::Display for RateLimitError {
 
 impl std::error::Error for RateLimitError {}
 
+/// Account behind an OAuth token, as reported by the profile endpoint.
+#[derive(Debug, Deserialize)]
+pub struct Account {
+    pub uuid: String,
+    #[serde(alias = "email_address")]
+    pub email: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Organization {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccountProfile {
+    pub account: Account,
+    pub organization: Option<Organization>,
+}
+
 pub struct ProfileInfo {
     pub plan_type: Option<String>,
 }
@@ -81,6 +98,19 @@ fn read_oauth(raw: &Value) -> Result<OAuthData> {
     Ok(serde_json::from_value(oauth_value.clone())?)
 }
 
+fn profile_info(oauth: &OAuthData) -> ProfileInfo {
+    ProfileInfo {
+        plan_type: oauth.subscription_type.clone().or(oauth.plan_type.clone()),
+    }
+}
+
+/// Reads plan information from a profile's credentials without touching the network.
+pub fn read_profile_info(path: &Path) -> Result<ProfileInfo> {
+    let content = fs::read_to_string(path)?;
+    let raw: Value = serde_json::from_str(&content)?;
+    Ok(profile_info(&read_oauth(&raw)?))
+}
+
 fn is_token_expired(oauth: &OAuthData) -> bool {
     match oauth.expires_at {
         // 5 minute buffer
@@ -188,17 +218,44 @@ pub async fn fetch_usage_with_token(token: &str) -> Result<UsageResponse> {
     Ok(resp.json().await?)
 }
 
+/// Extracts the OAuth access token from raw credentials content.
+pub fn access_token(credentials: &str) -> Option<String> {
+    let raw: Value = serde_json::from_str(credentials).ok()?;
+    read_oauth(&raw).ok().map(|oauth| oauth.access_token)
+}
+
+pub async fn fetch_account(token: &str) -> Result<AccountProfile> {
+    if token.is_empty() {
+        return Err(anyhow!("access token is empty"));
+    }
+
+    let resp = shared_client()
+        .get("https://api.anthropic.com/api/oauth/profile")
+        .header("Authorization", format!("Bearer {}", token))
+        .header("anthropic-beta", "oauth-2025-04-20")
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        return Err(anyhow!(
+            "profile API returned status {}: {}",
+            resp.status(),
+            resp.text().await.unwrap_or_default()
+        ));
+    }
+
+    Ok(resp.json().await?)
+}
+
 async fn get_access_token_from_credentials(
     path: &Path,
     is_current: bool,
 ) -> Result<(String, ProfileInfo)> {
     let content = tokio::fs::read_to_string(path).await?;
-    let mut raw: Value = serde_json::from_str(&content)?;
+    let raw: Value = serde_json::from_str(&content)?;
     let oauth = read_oauth(&raw)?;
 
-    let info = ProfileInfo {
-        plan_type: oauth.subscription_type.clone().or(oauth.plan_type.clone()),
-    };
+    let info = profile_info(&oauth);
 
     // For the current profile, always use the token as-is. aip is read-only
     // for the current profile; if the token is actually expired server-side,
@@ -208,29 +265,36 @@ async fn get_access_token_from_credentials(
         return Ok((oauth.access_token, info));
     }
 
-    let token_resp = refresh_token(&oauth)
+    let refreshed = refresh_stored_credentials(path)
         .await
         .context("Refresh token expired (switch to this profile to re-auth)")?;
-    let access_token = token_resp.access_token.clone();
-    apply_token_response(&mut raw, &token_resp)?;
-    let new_content = serde_json::to_string_pretty(&raw)?;
-    let path = path.to_owned();
-    tokio::task::spawn_blocking(move || {
-        fs_util::atomic_write(&path, &new_content)?;
-        #[cfg(unix)]
-        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
-        Ok::<(), anyhow::Error>(())
-    })
-    .await??;
-
-    Ok((access_token, info))
+    let refreshed: Value = serde_json::from_str(&refreshed)?;
+    Ok((read_oauth(&refreshed)?.access_token, info))
 }
 
 pub async fn refresh_credentials_if_expired(path: &Path) -> Result<String> {
     let content = tokio::fs::read_to_string(path).await?;
+    let raw: Value = serde_json::from_str(&content)?;
+    if !is_token_expired(&read_oauth(&raw)?) {
+        return Ok(content);
+    }
+    refresh_stored_credentials(path).await
+}
+
+/// Refreshes the credentials stored at `path` and returns them as stored
+/// afterwards. The profiles lock is held from the read through the write, so
+/// two aip processes never spend the same refresh token, and a token another
+/// process refreshed while this one waited is used as-is.
+async fn refresh_stored_credentials(path: &Path) -> Result<String> {
+    let read_path = path.to_owned();
+    let (lock, content) = tokio::task::spawn_blocking(move || {
+        let lock = Tool::Claude.lock_profiles()?;
+        let content = fs::read_to_string(&read_path)?;
+        Ok::<_, anyhow::Error>((lock, content))
+    })
+    .await??;
     let mut raw: Value = serde_json::from_str(&content)?;
     let oauth = read_oauth(&raw)?;
-
     if !is_token_expired(&oauth) {
         return Ok(content);
     }
@@ -241,30 +305,28 @@ pub async fn refresh_credentials_if_expired(path: &Path) -> Result<String> {
     let path = path.to_owned();
     let write_content = refreshed.clone();
     tokio::task::spawn_blocking(move || {
-        fs_util::atomic_write(&path, &write_content)?;
-        #[cfg(unix)]
-        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        let _lock = lock;
+        if let Some(profile_dir) = path.parent() {
+            backup::record(profile_dir, &content)?;
+        }
+        fs_util::atomic_write_private(&path, &write_content)?;
         Ok::<(), anyhow::Error>(())
     })
     .await??;
     Ok(refreshed)
 }
 
-pub async fn fetch_all_profiles_usage() -> HashMap<String, Result<(UsageResponse, ProfileInfo)>> {
-    // Sync Keychain credentials to current profile before fetching usage.
-    // Claude Code updates the Keychain directly when refreshing tokens,
-    // so the profile's credentials.json may be stale.
-    // Run on a blocking thread to avoid stalling the Tokio worker with
-    // the synchronous `security` subprocess call.
-    let _ = tokio::task::spawn_blocking(super::profile::sync_keychain_to_current_profile).await;
+pub async fn fetch_profiles_usage(
+    profiles: Vec<String>,
+) -> HashMap<String, Result<(UsageResponse, ProfileInfo)>> {
+    // Sync active credentials to current profile before fetching usage.
+    // Claude Code updates the Keychain (or ~/.claude/.credentials.json on
+    // Linux) directly when refreshing tokens, so the profile's
+    // credentials.json may be stale.
+    super::profile::sync_keychain_to_current_profile().await;
 
     let current_profile = Tool::Claude.current_profile().ok().flatten();
 
-    let profiles = match Tool::Claude.list_profiles() {
-        Ok(p) => p,
-        Err(_) => return HashMap::new(),
-    };
-
     let mut handles = Vec::new();
 
     for profile in profiles {
@@ -301,6 +363,30 @@ pub async fn fetch_all_profiles_usage() -> HashMap<String, Result<(UsageResponse
 mod tests {
     use super::*;
 
+    #[test]
+    fn access_token_reads_oauth_token() {
+        let creds = r#"{"claudeAiOauth": {"accessToken": "tok"}}"#;
+
+        assert_eq!(access_token(creds).as_deref(), Some("tok"));
+        assert_eq!(access_token("{}"), None);
+        assert_eq!(access_token("not json"), None);
+    }
+
+    #[test]
+    fn account_profile_response_parses_account() {
+        let profile: AccountProfile = serde_json::from_str(
+            r#"{"account": {"uuid": "acct-1", "email": "a@example.com"}, "organization": {"name": "Acme"}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(profile.account.uuid, "acct-1");
+        assert_eq!(profile.account.email.as_deref(), Some("a@example.com"));
+        assert_eq!(
+            profile.organization.and_then(|o| o.name).as_deref(),
+            Some("Acme")
+        );
+    }
+
     // --- apply_token_response tests ---
 
     #[test]
@@ -517,6 +603,20 @@ mod tests {
         assert!(oauth.plan_type.is_none());
     }
 
+    #[test]
+    fn read_profile_info_prefers_subscription_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("credentials.json");
+        std::fs::write(
+            &path,
+            r#"{"claudeAiOauth":{"accessToken":"a","subscriptionType":"max","planType":"pro"}}"#,
+        )
+        .unwrap();
+
+        let info = read_profile_info(&path).unwrap();
+        assert_eq!(info.plan_type.as_deref(), Some("max"));
+    }
+
     #[test]
     fn usage_response_accepts_null_resets_at() {
         let payload = r#"{
@@ -546,8 +646,7 @@ mod tests {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().join("credentials.json");
 
-        fs_util::atomic_write(&path, r#"{"claudeAiOauth":{}}"#).unwrap();
-        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600)).unwrap();
+        fs_util::atomic_write_private(&path, r#"{"claudeAiOauth":{}}"#).unwrap();
 
         let mode = std::fs::metadata(&path).unwrap().permissions().mode() & 0o777;
         assert_eq!(mode, 0o600, "credential file should be owner-only (0o600)");
@@ -1,6 +1,8 @@
 use std::ffi::{OsStr, OsString};
+use std::net::SocketAddr;
+use std::path::PathBuf;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser)]
 #[command(
@@ -28,6 +30,13 @@ where
     normalized
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum UsageFormat {
+    Table,
+    Json,
+    Oneline,
+}
+
 #[derive(Subcommand)]
 pub enum Command {
     /// Save current credentials as a new profile
@@ -37,12 +46,185 @@ pub enum Command {
         /// Profile name
         profile: Option<String>,
     },
+    /// List saved profiles
+    List {
+        /// Tool name (claude or codex)
+        tool: Option<String>,
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Set or clear a profile's note
+    Note {
+        /// Tool name (claude or codex)
+        tool: String,
+        /// Profile name
+        profile: String,
+        /// Note text; omit to clear
+        note: Option<String>,
+    },
+    /// Run a command with a profile's credentials without switching globally
+    Exec {
+        /// Tool name (claude or codex)
+        tool: String,
+        /// Profile name
+        profile: String,
+        /// Command to run, after `--`
+        #[arg(last = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Fetch usage once and print it
+    ///
+    /// Exits with 6 if `--fail-above` is given and any window is above it,
+    /// or with 8 if a profile has no usage data to check.
+    Usage {
+        /// Tool name (claude or codex)
+        tool: Option<String>,
+        /// Profile name
+        profile: Option<String>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = UsageFormat::Table)]
+        format: UsageFormat,
+        /// Fail when any usage window is above this used percentage
+        #[arg(long, value_name = "PERCENT")]
+        fail_above: Option<f64>,
+        /// Read the on-disk cache instead of fetching
+        #[arg(long)]
+        cached: bool,
+    },
+    /// Show recorded usage history
+    History {
+        /// Tool name (claude or codex)
+        tool: Option<String>,
+        /// Profile name
+        profile: Option<String>,
+        /// How far back to look, e.g. 12h, 7d or 2w
+        #[arg(long, default_value = "7d")]
+        since: String,
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Fetch usage periodically without the dashboard and print events
+    Watch {
+        /// Seconds between fetches; defaults to the dashboard refresh interval
+        #[arg(long, value_name = "SECONDS")]
+        interval: Option<u64>,
+        /// Print one JSON event per line
+        #[arg(long)]
+        json: bool,
+    },
+    /// Serve usage as Prometheus metrics and/or write them to a textfile
+    ServeMetrics {
+        /// Address to serve `/metrics` on [default: 127.0.0.1:9464 unless only --textfile is given]
+        #[arg(long, value_name = "ADDR")]
+        listen: Option<SocketAddr>,
+        /// Also write metrics to this file for node_exporter's textfile collector
+        #[arg(long, value_name = "PATH")]
+        textfile: Option<PathBuf>,
+        /// Seconds between fetches; defaults to the dashboard refresh interval
+        #[arg(long, value_name = "SECONDS")]
+        interval: Option<u64>,
+    },
+    /// Print the active profile's cached usage on one line, for Claude Code's statusLine
+    Statusline {
+        /// Tool name (claude or codex) [default: claude]
+        tool: Option<String>,
+    },
+    /// Roll a profile's credentials back to an automatic backup
+    Restore {
+        /// Tool name (claude or codex)
+        tool: String,
+        /// Profile name
+        profile: String,
+        /// Backup to restore, 1 being the most recent [default: 1]
+        #[arg(long, value_name = "N", conflicts_with = "list")]
+        version: Option<usize>,
+        /// List available backups instead of restoring
+        #[arg(long)]
+        list: bool,
+    },
+    /// List, restore or purge deleted profiles
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    /// Switch to a saved profile
+    ///
+    /// Exits with 3 if the profile does not exist, 4 if refreshing its token
+    /// failed and 5 if writing the active credentials failed.
+    Switch {
+        /// Tool name (claude or codex)
+        tool: String,
+        /// Profile name
+        #[arg(required_unless_present = "best")]
+        profile: Option<String>,
+        /// Fetch usage, rank profiles by remaining quota and switch to the best
+        #[arg(long, conflicts_with = "profile")]
+        best: bool,
+    },
+}
+
+impl Command {
+    /// Commands that only read profiles and usage. They skip recovering
+    /// interrupted switches, which may wait on the profiles lock, and leave it
+    /// to the next command that writes.
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self,
+            Command::List { .. }
+                | Command::History { .. }
+                | Command::Statusline { .. }
+                | Command::Usage { cached: true, .. }
+        )
+    }
+}
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// List deleted profiles
+    List {
+        /// Tool name (claude or codex)
+        tool: Option<String>,
+    },
+    /// Restore a deleted profile under its original name
+    Restore {
+        /// Tool name (claude or codex)
+        tool: String,
+        /// Trash entry, or a profile name for its most recent deletion
+        entry: String,
+    },
+    /// Permanently delete trashed profiles
+    Purge {
+        /// Tool name (claude or codex)
+        tool: Option<String>,
+        /// Skip the confirmation prompt
+        #[arg(short, long)]
+        yes: bool,
+    },
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn read_only_commands_are_recognized() {
+        let read_only = |args: &[&str]| {
+            Cli::try_parse_from(args)
+                .unwrap()
+                .command
+                .is_some_and(|c| c.is_read_only())
+        };
+
+        assert!(read_only(&["aip", "statusline"]));
+        assert!(read_only(&["aip", "list"]));
+        assert!(read_only(&["aip", "usage", "--cached"]));
+        assert!(!read_only(&["aip", "usage"]));
+        assert!(!read_only(&["aip", "switch", "claude", "work"]));
+        assert!(!read_only(&["aip"]));
+    }
+
     #[test]
     fn version_long_option_displays_version() {
         let parsed = Cli::try_parse_from(["aip", "--version"]);
@@ -83,6 +265,270 @@ mod tests {
         assert_eq!(error.kind(), clap::error::ErrorKind::DisplayHelp);
     }
 
+    #[test]
+    fn switch_requires_tool_and_profile() {
+        let parsed = Cli::try_parse_from(["aip", "switch", "claude", "work"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::Switch { ref tool, profile: Some(ref profile), best: false })
+                if tool == "claude" && profile == "work"
+        ));
+
+        assert!(Cli::try_parse_from(["aip", "switch", "claude"]).is_err());
+    }
+
+    #[test]
+    fn switch_best_replaces_profile() {
+        let parsed = Cli::try_parse_from(["aip", "switch", "codex", "--best"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::Switch {
+                profile: None,
+                best: true,
+                ..
+            })
+        ));
+
+        assert!(Cli::try_parse_from(["aip", "switch", "codex", "work", "--best"]).is_err());
+    }
+
+    #[test]
+    fn list_accepts_optional_tool_and_json_flag() {
+        let parsed = Cli::try_parse_from(["aip", "list", "codex", "--json"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::List { tool: Some(ref t), json: true }) if t == "codex"
+        ));
+
+        let parsed = Cli::try_parse_from(["aip", "list"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::List {
+                tool: None,
+                json: false
+            })
+        ));
+    }
+
+    #[test]
+    fn note_text_is_optional() {
+        let parsed = Cli::try_parse_from(["aip", "note", "claude", "work", "team seat"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::Note { note: Some(ref n), .. }) if n == "team seat"
+        ));
+
+        let parsed = Cli::try_parse_from(["aip", "note", "claude", "work"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::Note { note: None, .. })
+        ));
+    }
+
+    #[test]
+    fn exec_collects_command_after_separator() {
+        let parsed =
+            Cli::try_parse_from(["aip", "exec", "codex", "work", "--", "codex", "--help"]).unwrap();
+        match parsed.command {
+            Some(Command::Exec {
+                tool,
+                profile,
+                command,
+            }) => {
+                assert_eq!(tool, "codex");
+                assert_eq!(profile, "work");
+                assert_eq!(command, vec!["codex", "--help"]);
+            }
+            _ => panic!("expected exec command"),
+        }
+
+        assert!(Cli::try_parse_from(["aip", "exec", "codex", "work"]).is_err());
+    }
+
+    #[test]
+    fn usage_parses_format_and_threshold() {
+        let parsed = Cli::try_parse_from([
+            "aip",
+            "usage",
+            "claude",
+            "work",
+            "--format",
+            "oneline",
+            "--fail-above",
+            "90",
+        ])
+        .unwrap();
+        match parsed.command {
+            Some(Command::Usage {
+                tool,
+                profile,
+                format,
+                fail_above,
+                cached,
+            }) => {
+                assert!(!cached);
+                assert_eq!(tool.as_deref(), Some("claude"));
+                assert_eq!(profile.as_deref(), Some("work"));
+                assert_eq!(format, UsageFormat::Oneline);
+                assert_eq!(fail_above, Some(90.0));
+            }
+            _ => panic!("expected usage command"),
+        }
+
+        let parsed = Cli::try_parse_from(["aip", "usage"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::Usage {
+                format: UsageFormat::Table,
+                fail_above: None,
+                ..
+            })
+        ));
+
+        let parsed = Cli::try_parse_from(["aip", "usage", "--cached"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::Usage { cached: true, .. })
+        ));
+    }
+
+    #[test]
+    fn history_defaults_to_last_week() {
+        let parsed =
+            Cli::try_parse_from(["aip", "history", "claude", "work", "--since", "12h"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::History { tool: Some(ref t), profile: Some(ref p), ref since, json: false })
+                if t == "claude" && p == "work" && since == "12h"
+        ));
+
+        let parsed = Cli::try_parse_from(["aip", "history"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::History { tool: None, ref since, .. }) if since == "7d"
+        ));
+    }
+
+    #[test]
+    fn watch_accepts_interval_and_json() {
+        let parsed = Cli::try_parse_from(["aip", "watch", "--interval", "30", "--json"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::Watch {
+                interval: Some(30),
+                json: true
+            })
+        ));
+
+        let parsed = Cli::try_parse_from(["aip", "watch"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::Watch {
+                interval: None,
+                json: false
+            })
+        ));
+    }
+
+    #[test]
+    fn serve_metrics_parses_listen_and_textfile() {
+        let parsed = Cli::try_parse_from([
+            "aip",
+            "serve-metrics",
+            "--listen",
+            "0.0.0.0:9000",
+            "--textfile",
+            "/tmp/aip.prom",
+        ])
+        .unwrap();
+        match parsed.command {
+            Some(Command::ServeMetrics {
+                listen,
+                textfile,
+                interval,
+            }) => {
+                assert_eq!(listen, Some("0.0.0.0:9000".parse().unwrap()));
+                assert_eq!(textfile, Some(PathBuf::from("/tmp/aip.prom")));
+                assert_eq!(interval, None);
+            }
+            _ => panic!("expected serve-metrics command"),
+        }
+
+        assert!(Cli::try_parse_from(["aip", "serve-metrics", "--listen", "nope"]).is_err());
+    }
+
+    #[test]
+    fn statusline_tool_is_optional() {
+        let parsed = Cli::try_parse_from(["aip", "statusline"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::Statusline { tool: None })
+        ));
+    }
+
+    #[test]
+    fn restore_accepts_version_or_list() {
+        let parsed =
+            Cli::try_parse_from(["aip", "restore", "codex", "work", "--version", "2"]).unwrap();
+        match parsed.command {
+            Some(Command::Restore {
+                tool,
+                profile,
+                version,
+                list,
+            }) => {
+                assert_eq!(tool, "codex");
+                assert_eq!(profile, "work");
+                assert_eq!(version, Some(2));
+                assert!(!list);
+            }
+            _ => panic!("expected restore"),
+        }
+
+        assert!(
+            Cli::try_parse_from([
+                "aip",
+                "restore",
+                "codex",
+                "work",
+                "--list",
+                "--version",
+                "1"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn trash_subcommands_parse() {
+        let parsed = Cli::try_parse_from(["aip", "trash", "list"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::Trash {
+                action: TrashAction::List { tool: None }
+            })
+        ));
+
+        let parsed = Cli::try_parse_from(["aip", "trash", "restore", "codex", "work"]).unwrap();
+        match parsed.command {
+            Some(Command::Trash {
+                action: TrashAction::Restore { tool, entry },
+            }) => {
+                assert_eq!(tool, "codex");
+                assert_eq!(entry, "work");
+            }
+            _ => panic!("expected trash restore"),
+        }
+
+        let parsed = Cli::try_parse_from(["aip", "trash", "purge", "claude", "-y"]).unwrap();
+        assert!(matches!(
+            parsed.command,
+            Some(Command::Trash {
+                action: TrashAction::Purge { yes: true, .. }
+            })
+        ));
+    }
+
     #[test]
     fn normalize_short_flags_converts_short_help_to_long_help() {
         let normalized = normalize_short_flags(["aip", "-h"]);
@@ -1,91 +1,34 @@
-use std::fs;
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
 
-use anyhow::{Result, anyhow};
+use anyhow::Result;
 
-use crate::fs_util;
+use super::usage;
+use crate::credential_store::FileStore;
+use crate::meta::ProfileMeta;
+use crate::profile::{ProfileManager, Recovery};
 use crate::tool::Tool;
 
 const TOOL: Tool = Tool::Codex;
+const AUTH_FILE: &str = "auth.json";
 
-pub fn switch(profile: &str) -> Result<()> {
-    let profile_dir = TOOL.profile_dir(profile)?;
-    if !profile_dir.exists() {
-        return Err(anyhow!("profile '{}' does not exist for {}", profile, TOOL));
-    }
-
-    // Save active auth.json to current profile
-    sync_auth_to_current_profile();
-
-    // Validate new profile's auth.json exists before making changes
-    let src = profile_dir.join("auth.json");
-    if !src.exists() {
-        return Err(anyhow!(
-            "credentials file not found for profile '{}' ({})",
-            profile,
-            TOOL
-        ));
-    }
-
-    // Pre-compute all fallible paths before the point of no return.
-    let dest = TOOL.home_dir()?.join("auth.json");
-
-    // Update _current first, then copy credentials.
-    // If credential copy fails, roll back _current to avoid contamination.
-    let current_file = TOOL.current_file()?;
-    let old_current = fs::read_to_string(&current_file).ok();
-    fs_util::atomic_write(&current_file, &format!("{}\n", profile))?;
-    if let Err(e) = fs_util::atomic_copy(&src, &dest) {
-        // Roll back _current to previous value
-        match &old_current {
-            Some(prev) => {
-                let _ = fs_util::atomic_write(&current_file, prev);
-            }
-            None => {
-                let _ = fs::remove_file(&current_file);
-            }
-        }
-        return Err(e);
-    }
-
-    Ok(())
+fn manager() -> Result<ProfileManager> {
+    let store = FileStore::new(TOOL.home_dir()?.join(AUTH_FILE), AUTH_FILE);
+    Ok(ProfileManager::for_tool(TOOL, Box::new(store))?.with_sync_guard(account_ids_match))
 }
 
-pub fn sync_auth_to_current_profile() {
-    let current = match TOOL.current_profile() {
-        Ok(Some(name)) => name,
-        _ => return,
-    };
-    let dest = match TOOL.profile_dir(&current) {
-        Ok(dir) => dir.join("auth.json"),
-        _ => return,
-    };
-    let src = match TOOL.home_dir() {
-        Ok(dir) => dir.join("auth.json"),
-        _ => return,
-    };
-    if !src.exists() {
-        return;
-    }
+fn account_id(data: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(data).ok()?;
+    value
+        .get("tokens")?
+        .get("account_id")?
+        .as_str()
+        .map(str::to_string)
+}
 
-    let src_value: Option<serde_json::Value> = fs::read_to_string(&src)
-        .ok()
-        .and_then(|s| serde_json::from_str(&s).ok());
-    let dest_value: Option<serde_json::Value> = fs::read_to_string(&dest)
-        .ok()
-        .and_then(|s| serde_json::from_str(&s).ok());
-
-    let src_account = src_value
-        .as_ref()
-        .and_then(|v| v.get("tokens"))
-        .and_then(|t| t.get("account_id"))
-        .and_then(|a| a.as_str());
-    let dest_account = dest_value
-        .as_ref()
-        .and_then(|v| v.get("tokens"))
-        .and_then(|t| t.get("account_id"))
-        .and_then(|a| a.as_str());
+/// Refuses to overwrite a profile with credentials from a different account.
+fn account_ids_match(current: &str, src: &str, dest: Option<&str>) -> bool {
+    let src_account = account_id(src);
+    let dest_account = dest.and_then(account_id);
 
     match (src_account, dest_account) {
         (Some(src_id), Some(dest_id)) if src_id != dest_id => {
@@ -95,7 +38,7 @@ pub fn sync_auth_to_current_profile() {
             );
             eprintln!("Skipping sync to protect stored credentials.");
             eprintln!("Re-authenticate and run 'aip save' to save to the correct profile.");
-            return;
+            false
         }
         (Some(_), None) => {
             // Source has account_id but dest doesn't (e.g. after Codex CLI structural changes).
@@ -104,6 +47,7 @@ pub fn sync_auth_to_current_profile() {
                 "Warning: Current auth.json has account_id but profile '{}' does not. Syncing anyway.",
                 current,
             );
+            true
         }
         (None, Some(_)) => {
             // Dest has account_id but source doesn't; block to avoid overwriting
@@ -112,305 +56,224 @@ pub fn sync_auth_to_current_profile() {
                 "Warning: Profile '{}' has account_id but current auth.json does not. Skipping sync.",
                 current,
             );
-            return;
+            false
         }
-        _ => {}
+        _ => true,
     }
+}
 
-    if let Err(e) = fs_util::atomic_copy(&src, &dest) {
-        eprintln!(
-            "Warning: failed to sync auth to profile '{}': {}",
-            current, e
-        );
-    }
+/// Manager whose active credentials live in an isolated `CODEX_HOME`.
+pub fn isolated_manager(codex_home: &Path) -> Result<ProfileManager> {
+    let store = FileStore::new(codex_home.join(AUTH_FILE), AUTH_FILE);
+    Ok(ProfileManager::for_tool(TOOL, Box::new(store))?.with_sync_guard(account_ids_match))
 }
 
-pub fn save(name: &str) -> Result<()> {
-    let src = TOOL.home_dir()?.join("auth.json");
-    if !src.exists() {
-        return Err(anyhow!("auth.json not found in {}", TOOL));
-    }
+/// Syncs credentials refreshed inside an isolated `CODEX_HOME` back into
+/// `profile`, unless the profile changed since `activated` was copied in.
+pub fn sync_isolated_to_profile(codex_home: &Path, profile: &str, activated: &str) {
+    let Ok(manager) = isolated_manager(codex_home) else {
+        return;
+    };
+    let Ok(_lock) = TOOL.lock_profiles() else {
+        return;
+    };
+    manager.sync_active_to_unchanged_profile(profile, activated);
+}
 
-    let dest_dir = TOOL.profile_dir(name)?;
-    let newly_created = !dest_dir.exists();
-    fs::create_dir_all(&dest_dir)?;
+/// Records the account id and `id_token` email from `profile`'s auth.json in
+/// its metadata.
+fn record_account(profile: &str) {
+    let Ok(dir) = TOOL.profile_dir(profile) else {
+        return;
+    };
+    let auth_path = dir.join(AUTH_FILE);
+    let Ok(content) = std::fs::read_to_string(&auth_path) else {
+        return;
+    };
+    let id = account_id(&content);
+    let email = usage::read_id_token_claims(&auth_path)
+        .ok()
+        .and_then(|claims| claims.email);
+    let _ = ProfileMeta::update(&dir, |m| {
+        if id.is_some() {
+            m.account_id = id;
+        }
+        if email.is_some() {
+            m.email = email;
+        }
+    });
+}
 
-    let result = (|| -> Result<()> {
-        let dest_path = dest_dir.join("auth.json");
-        fs_util::atomic_copy(&src, &dest_path)?;
-        #[cfg(unix)]
-        fs::set_permissions(&dest_path, fs::Permissions::from_mode(0o600))?;
+pub fn switch(profile: &str) -> Result<()> {
+    let manager = manager()?;
+    let _lock = TOOL.lock_profiles()?;
+    manager.switch(profile)?;
+    record_account(profile);
+    Ok(())
+}
+
+pub fn sync_auth_to_current_profile() {
+    let Ok(manager) = manager() else {
+        return;
+    };
+    let Ok(_lock) = TOOL.lock_profiles() else {
+        return;
+    };
+    manager.sync_active_to_current_profile();
+}
 
-        // Update current profile to the newly saved one
-        let current_file = TOOL.current_file()?;
-        fs_util::atomic_write(&current_file, &format!("{}\n", name))?;
+pub fn save(name: &str) -> Result<()> {
+    let manager = manager()?;
+    let _lock = TOOL.lock_profiles()?;
+    manager.save(name)?;
+    record_account(name);
+    Ok(())
+}
 
-        Ok(())
-    })();
+pub fn restore(profile: &str, version: usize) -> Result<()> {
+    let manager = manager()?;
+    let _lock = TOOL.lock_profiles()?;
+    manager.restore(profile, version)
+}
 
-    if result.is_err() && newly_created {
-        let _ = fs::remove_dir_all(&dest_dir);
+/// Finishes or rolls back a switch interrupted by a killed aip.
+pub fn recover_switch() -> Result<Option<Recovery>> {
+    let manager = manager()?;
+    if !manager.has_pending_switch() {
+        return Ok(None);
     }
-
-    result
+    let _lock = TOOL.lock_profiles()?;
+    manager.recover()
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
     use super::*;
+    use crate::credential_store::{CredentialStore, MemoryStore};
+
+    /// A manager with Codex's sync guard over an in-memory store.
+    fn setup(store: MemoryStore) -> (tempfile::TempDir, MemoryStore, ProfileManager) {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ProfileManager::new(TOOL, dir.path().to_path_buf(), Box::new(store.clone()))
+            .with_sync_guard(account_ids_match);
+        (dir, store, manager)
+    }
+
+    fn add_profile(dir: &Path, store: &MemoryStore, name: &str, data: &str) {
+        let profile_dir = dir.join(name);
+        fs::create_dir_all(&profile_dir).unwrap();
+        store.write_profile(&profile_dir, data).unwrap();
+    }
 
-    /// Simulate the switch rollback logic: if credential copy after _current
-    /// update fails, _current must be restored to its previous value.
     #[test]
     fn switch_rolls_back_current_on_copy_failure() {
-        let dir = tempfile::tempdir().unwrap();
-        let current_file = dir.path().join("_current");
-
-        // Pre-existing _current value
-        fs::write(&current_file, "old-profile\n").unwrap();
-
-        let old_current = fs::read_to_string(&current_file).ok();
-        fs_util::atomic_write(&current_file, "new-profile\n").unwrap();
-        assert_eq!(fs::read_to_string(&current_file).unwrap(), "new-profile\n");
-
-        // Simulate atomic_copy failure -> rollback
-        let copy_failed = true;
-        if copy_failed {
-            match &old_current {
-                Some(prev) => {
-                    let _ = fs_util::atomic_write(&current_file, prev);
-                }
-                None => {
-                    let _ = fs::remove_file(&current_file);
-                }
-            }
-        }
+        let (dir, store, manager) =
+            setup(MemoryStore::with_active("old-auth").failing_active_writes());
+        add_profile(dir.path(), &store, "old-profile", "old-auth");
+        add_profile(dir.path(), &store, "new-profile", "new-auth");
+        fs::write(dir.path().join("_current"), "old-profile\n").unwrap();
+
+        assert!(manager.switch("new-profile").is_err());
 
-        assert_eq!(fs::read_to_string(&current_file).unwrap(), "old-profile\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("_current")).unwrap(),
+            "old-profile\n"
+        );
+        assert_eq!(store.active().as_deref(), Some("old-auth"));
     }
 
-    /// When _current didn't exist before switch, rollback should remove it.
     #[test]
     fn switch_removes_current_on_rollback_when_no_previous() {
-        let dir = tempfile::tempdir().unwrap();
-        let current_file = dir.path().join("_current");
-
-        let old_current: Option<String> = fs::read_to_string(&current_file).ok();
-        assert!(old_current.is_none());
-
-        fs_util::atomic_write(&current_file, "new-profile\n").unwrap();
-        assert!(current_file.exists());
-
-        // Simulate failure -> rollback
-        match &old_current {
-            Some(prev) => {
-                let _ = fs_util::atomic_write(&current_file, prev);
-            }
-            None => {
-                let _ = fs::remove_file(&current_file);
-            }
-        }
+        let (dir, store, manager) = setup(MemoryStore::default().failing_active_writes());
+        add_profile(dir.path(), &store, "new-profile", "new-auth");
 
-        assert!(!current_file.exists());
+        assert!(manager.switch("new-profile").is_err());
+
+        assert!(!dir.path().join("_current").exists());
     }
 
-    /// Simulate save cleanup: newly created profile dir is removed on failure.
     #[test]
     fn save_cleans_up_newly_created_dir_on_failure() {
-        let dir = tempfile::tempdir().unwrap();
-        let dest_dir = dir.path().join("profiles").join("new-profile");
-        let newly_created = !dest_dir.exists();
-        assert!(newly_created);
-
-        fs::create_dir_all(&dest_dir).unwrap();
-        assert!(dest_dir.exists());
+        let (dir, _store, manager) = setup(MemoryStore::default());
 
-        // Simulate failure in the inner closure
-        let result: Result<()> = Err(anyhow!("simulated copy failure"));
+        assert!(manager.save("new-profile").is_err());
 
-        if result.is_err() && newly_created {
-            let _ = fs::remove_dir_all(&dest_dir);
-        }
-
-        assert!(!dest_dir.exists());
+        assert!(!dir.path().join("new-profile").exists());
     }
 
-    /// Verify that dest path is resolved before _current update so that
-    /// a failure in home_dir() cannot leave _current in a dirty state.
     #[test]
-    fn switch_dest_resolved_before_current_update() {
-        // This test validates the ordering guarantee: all fallible path
-        // computations (like home_dir()) happen before the _current write.
-        // We simulate the structure by showing that dest is available before
-        // the current_file write occurs.
-        let dir = tempfile::tempdir().unwrap();
-        let current_file = dir.path().join("_current");
-        let auth_dir = dir.path().join("home");
-        fs::create_dir_all(&auth_dir).unwrap();
-        let dest = auth_dir.join("auth.json");
-
-        // dest is computed successfully
-        assert!(dest.parent().unwrap().exists());
-
-        // Now write _current -- if dest computation had failed above,
-        // we would never reach this point (mirroring the real code's ? operator).
-        fs::write(&current_file, "old-profile\n").unwrap();
-        let old_current = fs::read_to_string(&current_file).ok();
-        fs_util::atomic_write(&current_file, "new-profile\n").unwrap();
-
-        // Simulate copy failure -> rollback
-        match &old_current {
-            Some(prev) => {
-                let _ = fs_util::atomic_write(&current_file, prev);
-            }
-            None => {
-                let _ = fs::remove_file(&current_file);
-            }
-        }
+    fn save_preserves_existing_dir_on_failure() {
+        let (dir, store, manager) = setup(MemoryStore::default());
+        add_profile(dir.path(), &store, "existing-profile", "old-auth");
+
+        assert!(manager.save("existing-profile").is_err());
 
-        assert_eq!(fs::read_to_string(&current_file).unwrap(), "old-profile\n");
+        assert_eq!(
+            store
+                .profile(&dir.path().join("existing-profile"))
+                .as_deref(),
+            Some("old-auth")
+        );
     }
 
-    /// sync_auth_to_current_profile should proceed (with warning) when source
-    /// has account_id but dest does not.
     #[test]
     fn sync_allows_copy_when_src_has_account_id_but_dest_does_not() {
-        let dir = tempfile::tempdir().unwrap();
-        let src_path = dir.path().join("src_auth.json");
-        let dest_path = dir.path().join("dest_auth.json");
-
-        // Source has account_id
-        let src_json = serde_json::json!({
-            "tokens": {
-                "account_id": "acct-123",
-                "access_token": "new-token"
-            }
-        });
-        fs::write(&src_path, serde_json::to_string_pretty(&src_json).unwrap()).unwrap();
-
-        // Dest has NO account_id
-        let dest_json = serde_json::json!({
-            "tokens": {
-                "access_token": "old-token"
-            }
-        });
-        fs::write(
-            &dest_path,
-            serde_json::to_string_pretty(&dest_json).unwrap(),
-        )
-        .unwrap();
-
-        // Simulate the match logic from sync_auth_to_current_profile
-        let src_value: serde_json::Value =
-            serde_json::from_str(&fs::read_to_string(&src_path).unwrap()).unwrap();
-        let dest_value: serde_json::Value =
-            serde_json::from_str(&fs::read_to_string(&dest_path).unwrap()).unwrap();
-
-        let src_account = src_value
-            .get("tokens")
-            .and_then(|t| t.get("account_id"))
-            .and_then(|a| a.as_str());
-        let dest_account = dest_value
-            .get("tokens")
-            .and_then(|t| t.get("account_id"))
-            .and_then(|a| a.as_str());
-
-        assert_eq!(src_account, Some("acct-123"));
-        assert_eq!(dest_account, None);
-
-        // (Some, None) case should NOT block -- sync proceeds
-        let should_sync = match (src_account, dest_account) {
-            (Some(_src_id), Some(dest_id)) if src_account.unwrap() != dest_id => false,
-            (None, Some(_)) => false,
-            _ => true, // includes (Some(_), None), (None, None), (Some(x), Some(x))
-        };
-        assert!(should_sync, "(Some, None) must allow sync to proceed");
-
-        // Simulate the copy
-        fs_util::atomic_copy(&src_path, &dest_path).unwrap();
-        let updated: serde_json::Value =
-            serde_json::from_str(&fs::read_to_string(&dest_path).unwrap()).unwrap();
-        assert_eq!(updated["tokens"]["account_id"].as_str(), Some("acct-123"),);
+        let src = r#"{"tokens":{"account_id":"acct-123","access_token":"new-token"}}"#;
+        let (dir, store, manager) = setup(MemoryStore::with_active(src));
+        add_profile(
+            dir.path(),
+            &store,
+            "work",
+            r#"{"tokens":{"access_token":"old-token"}}"#,
+        );
+
+        manager.sync_active_to_profile("work");
+
+        assert_eq!(
+            store.profile(&dir.path().join("work")).as_deref(),
+            Some(src)
+        );
     }
 
-    /// sync_auth_to_current_profile should block when dest has account_id
-    /// but source does not.
     #[test]
     fn sync_blocks_copy_when_dest_has_account_id_but_src_does_not() {
-        let dir = tempfile::tempdir().unwrap();
-        let src_path = dir.path().join("src_auth.json");
-        let dest_path = dir.path().join("dest_auth.json");
-
-        // Source has NO account_id
-        let src_json = serde_json::json!({
-            "tokens": {
-                "access_token": "anon-token"
-            }
-        });
-        fs::write(&src_path, serde_json::to_string_pretty(&src_json).unwrap()).unwrap();
-
-        // Dest has account_id
-        let dest_json = serde_json::json!({
-            "tokens": {
-                "account_id": "acct-456",
-                "access_token": "identified-token"
-            }
-        });
-        let original_dest = serde_json::to_string_pretty(&dest_json).unwrap();
-        fs::write(&dest_path, &original_dest).unwrap();
-
-        let src_value: serde_json::Value =
-            serde_json::from_str(&fs::read_to_string(&src_path).unwrap()).unwrap();
-        let dest_value: serde_json::Value =
-            serde_json::from_str(&fs::read_to_string(&dest_path).unwrap()).unwrap();
-
-        let src_account = src_value
-            .get("tokens")
-            .and_then(|t| t.get("account_id"))
-            .and_then(|a| a.as_str());
-        let dest_account = dest_value
-            .get("tokens")
-            .and_then(|t| t.get("account_id"))
-            .and_then(|a| a.as_str());
-
-        assert_eq!(src_account, None);
-        assert_eq!(dest_account, Some("acct-456"));
-
-        // (None, Some) case MUST block sync
-        let should_sync = match (src_account, dest_account) {
-            (Some(_src_id), Some(dest_id)) if src_account.unwrap() != dest_id => false,
-            (None, Some(_)) => false,
-            _ => true,
-        };
-        assert!(!should_sync, "(None, Some) must block sync");
-
-        // Dest should remain unchanged
-        assert_eq!(fs::read_to_string(&dest_path).unwrap(), original_dest);
-    }
+        let dest = r#"{"tokens":{"account_id":"acct-456","access_token":"identified-token"}}"#;
+        let (dir, store, manager) = setup(MemoryStore::with_active(
+            r#"{"tokens":{"access_token":"anon-token"}}"#,
+        ));
+        add_profile(dir.path(), &store, "work", dest);
 
-    /// When save overwrites an existing profile, dir should NOT be removed on failure.
-    #[test]
-    fn save_preserves_existing_dir_on_failure() {
-        let dir = tempfile::tempdir().unwrap();
-        let dest_dir = dir.path().join("profiles").join("existing-profile");
-        fs::create_dir_all(&dest_dir).unwrap();
-        fs::write(dest_dir.join("auth.json"), "old-auth").unwrap();
+        manager.sync_active_to_profile("work");
 
-        let newly_created = !dest_dir.exists();
-        assert!(!newly_created);
+        assert_eq!(
+            store.profile(&dir.path().join("work")).as_deref(),
+            Some(dest)
+        );
+    }
 
-        // Simulate failure
-        let result: Result<()> = Err(anyhow!("simulated copy failure"));
+    #[test]
+    fn account_ids_match_blocks_different_accounts() {
+        let src = r#"{"tokens":{"account_id":"acct-1"}}"#;
+        let dest = r#"{"tokens":{"account_id":"acct-2"}}"#;
+        assert!(!account_ids_match("work", src, Some(dest)));
+    }
 
-        if result.is_err() && newly_created {
-            let _ = fs::remove_dir_all(&dest_dir);
-        }
+    #[test]
+    fn account_ids_match_allows_same_account() {
+        let src = r#"{"tokens":{"account_id":"acct-1","access_token":"new"}}"#;
+        let dest = r#"{"tokens":{"account_id":"acct-1","access_token":"old"}}"#;
+        assert!(account_ids_match("work", src, Some(dest)));
+    }
 
-        // Directory and contents preserved
-        assert!(dest_dir.exists());
-        assert_eq!(
-            fs::read_to_string(dest_dir.join("auth.json")).unwrap(),
-            "old-auth"
-        );
+    #[test]
+    fn account_ids_match_handles_missing_ids() {
+        let with_id = r#"{"tokens":{"account_id":"acct-1"}}"#;
+        let without_id = r#"{"tokens":{}}"#;
+        assert!(account_ids_match("work", with_id, Some(without_id)));
+        assert!(!account_ids_match("work", without_id, Some(with_id)));
+        assert!(account_ids_match("work", without_id, None));
+        assert!(account_ids_match("work", with_id, None));
     }
 }
@@ -1,16 +1,16 @@
 use std::fs;
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 
 use anyhow::{Result, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use serde_json::Value;
 
-use crate::fs_util;
 use crate::http::shared_client;
 use crate::tool::Tool;
+use crate::{backup, fs_util};
 
 // These constants are reverse-engineered from the Codex CLI binary.
 // They may need updating when the upstream tool changes.
@@ -60,6 +60,47 @@ struct RefreshResponse {
     id_token: Option<String>,
 }
 
+/// Claims aip cares about from the ChatGPT `id_token` stored in auth.json.
+#[derive(Debug, Default, PartialEq)]
+pub struct IdTokenClaims {
+    pub email: Option<String>,
+    pub plan_type: Option<String>,
+}
+
+/// Decodes the payload of a JWT without verifying its signature.
+fn decode_jwt_payload(jwt: &str) -> Option<Value> {
+    let payload = jwt.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+fn parse_id_token(jwt: &str) -> Option<IdTokenClaims> {
+    let claims = decode_jwt_payload(jwt)?;
+    let auth = claims.get("https://api.openai.com/auth");
+    let profile = claims.get("https://api.openai.com/profile");
+    let string_at = |v: Option<&Value>, key: &str| {
+        v.and_then(|v| v.get(key))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    Some(IdTokenClaims {
+        email: string_at(Some(&claims), "email").or_else(|| string_at(profile, "email")),
+        plan_type: string_at(auth, "chatgpt_plan_type"),
+    })
+}
+
+/// Reads plan and email from the `id_token` in an auth.json, offline.
+pub fn read_id_token_claims(path: &Path) -> Result<IdTokenClaims> {
+    let content = fs::read_to_string(path)?;
+    let raw: Value = serde_json::from_str(&content)?;
+    let id_token = raw
+        .get("tokens")
+        .and_then(|t| t.get("id_token"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("no id_token in auth.json"))?;
+    parse_id_token(id_token).ok_or_else(|| anyhow!("malformed id_token in auth.json"))
+}
+
 fn read_tokens(raw: &Value) -> Result<TokenData> {
     let tokens_value = raw
         .get("tokens")
@@ -137,17 +178,24 @@ async fn parse_usage_response(resp: reqwest::Response) -> Result<Option<RateLimi
     Ok(usage.rate_limit)
 }
 
-async fn fetch_from_auth_path(path: &Path) -> Result<Option<RateLimits>> {
-    let (mut raw, tokens) = read_auth(path).await?;
-
-    let resp = fetch_usage_api(&tokens).await?;
-
-    match resp.status() {
-        reqwest::StatusCode::UNAUTHORIZED => {}
-        _ => return parse_usage_response(resp).await,
+/// Refreshes the auth.json at `path` after `rejected` was refused, returning
+/// its contents as stored afterwards. The profiles lock is held from the
+/// read through the write, so two aip processes never spend the same refresh
+/// token; if another process already replaced `rejected`, its tokens are used.
+async fn refresh_stored_auth(path: &Path, rejected: &str) -> Result<Value> {
+    let read_path = path.to_owned();
+    let (lock, content) = tokio::task::spawn_blocking(move || {
+        let lock = Tool::Codex.lock_profiles()?;
+        let content = fs::read_to_string(&read_path)?;
+        Ok::<_, anyhow::Error>((lock, content))
+    })
+    .await??;
+    let mut raw: Value = serde_json::from_str(&content)?;
+    let tokens = read_tokens(&raw)?;
+    if tokens.access_token != rejected {
+        return Ok(raw);
     }
 
-    // Token expired, try refreshing
     let refresh_token = tokens
         .refresh_token
         .as_deref()
@@ -166,12 +214,32 @@ async fn fetch_from_auth_path(path: &Path) -> Result<Option<RateLimits>> {
     let path = path.to_owned();
     let serialized = serde_json::to_string_pretty(&raw)?;
     tokio::task::spawn_blocking(move || {
-        fs_util::atomic_write(&path, &serialized)?;
-        #[cfg(unix)]
-        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        let _lock = lock;
+        // Only stored profiles keep backups, not the active auth.json.
+        if path.starts_with(Tool::Codex.profiles_dir()?)
+            && let Some(profile_dir) = path.parent()
+        {
+            backup::record(profile_dir, &content)?;
+        }
+        fs_util::atomic_write_private(&path, &serialized)?;
         Ok::<(), anyhow::Error>(())
     })
     .await??;
+    Ok(raw)
+}
+
+async fn fetch_from_auth_path(path: &Path) -> Result<Option<RateLimits>> {
+    let (_, tokens) = read_auth(path).await?;
+
+    let resp = fetch_usage_api(&tokens).await?;
+
+    match resp.status() {
+        reqwest::StatusCode::UNAUTHORIZED => {}
+        _ => return parse_usage_response(resp).await,
+    }
+
+    // Token expired, try refreshing
+    let raw = refresh_stored_auth(path, &tokens.access_token).await?;
 
     let new_tokens = read_tokens(&raw)?;
     let resp = fetch_usage_api(&new_tokens).await?;
@@ -207,6 +275,64 @@ pub async fn fetch_usage_from_auth(path: &Path) -> Result<Option<RateLimits>> {
 mod tests {
     use super::*;
 
+    fn make_jwt(claims: Value) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims).unwrap());
+        format!("eyJhbGciOiJub25lIn0.{}.sig", payload)
+    }
+
+    #[test]
+    fn parse_id_token_reads_plan_and_email() {
+        let jwt = make_jwt(serde_json::json!({
+            "email": "dev@example.com",
+            "https://api.openai.com/auth": { "chatgpt_plan_type": "plus" }
+        }));
+
+        assert_eq!(
+            parse_id_token(&jwt),
+            Some(IdTokenClaims {
+                email: Some("dev@example.com".to_string()),
+                plan_type: Some("plus".to_string()),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_id_token_falls_back_to_profile_email() {
+        let jwt = make_jwt(serde_json::json!({
+            "https://api.openai.com/profile": { "email": "p@example.com" }
+        }));
+
+        let claims = parse_id_token(&jwt).unwrap();
+        assert_eq!(claims.email.as_deref(), Some("p@example.com"));
+        assert_eq!(claims.plan_type, None);
+    }
+
+    #[test]
+    fn parse_id_token_rejects_malformed_tokens() {
+        assert_eq!(parse_id_token("not-a-jwt"), None);
+        assert_eq!(parse_id_token("a.!!!.c"), None);
+    }
+
+    #[test]
+    fn read_id_token_claims_from_auth_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("auth.json");
+        let jwt = make_jwt(serde_json::json!({
+            "https://api.openai.com/auth": { "chatgpt_plan_type": "pro" }
+        }));
+        fs::write(
+            &path,
+            serde_json::json!({ "tokens": { "access_token": "a", "id_token": jwt } }).to_string(),
+        )
+        .unwrap();
+
+        let claims = read_id_token_claims(&path).unwrap();
+        assert_eq!(claims.plan_type.as_deref(), Some("pro"));
+
+        fs::write(&path, r#"{"tokens": {"access_token": "a"}}"#).unwrap();
+        assert!(read_id_token_claims(&path).is_err());
+    }
+
     #[test]
     fn read_tokens_with_refresh_token() {
         let raw: Value = serde_json::json!({
@@ -257,8 +383,7 @@ mod tests {
         let dir = tempfile::tempdir().unwrap();
         let path = dir.path().join("auth.json");
 
-        fs_util::atomic_write(&path, r#"{"tokens":{}}"#).unwrap();
-        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        fs_util::atomic_write_private(&path, r#"{"tokens":{}}"#).unwrap();
 
         let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
         assert_eq!(mode, 0o600, "credential file should be owner-only (0o600)");
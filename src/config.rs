@@ -1,40 +1,159 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+use std::time::Duration;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 
+use crate::auto_switch::AutoSwitchPolicy;
 use crate::display::DisplayPreference;
 use crate::fs_util::atomic_write;
+use crate::notify::NotificationRule;
+use crate::tool::Tool;
 
 #[derive(Serialize, Deserialize, Default)]
 pub struct Config {
     #[serde(default)]
     pub display_mode: DisplayPreference,
+    /// Dashboard auto-refresh interval in seconds; `0` disables it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_interval_secs: Option<u64>,
+    /// How old cached usage may be before it is ignored, in seconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage_cache_max_age_secs: Option<u64>,
+    /// Days deleted profiles stay in the trash; `0` keeps them forever.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trash_retention_days: Option<u64>,
+    /// Days of usage samples kept in `history.jsonl`; `0` keeps them forever.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history_retention_days: Option<u64>,
+    /// Previous credential versions kept per profile; `0` disables backups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_backups: Option<usize>,
+    /// Switches away from a profile near its limits when set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_switch: Option<AutoSwitchPolicy>,
+    /// Hook commands run when usage crosses a threshold or a window resets.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notifications: Vec<NotificationRule>,
+    /// Overrides `~/.claude` when `CLAUDE_CONFIG_DIR` is not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claude_home: Option<PathBuf>,
+    /// Overrides `~/.codex` when `CODEX_HOME` is not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codex_home: Option<PathBuf>,
 }
 
-fn config_path() -> Result<PathBuf> {
-    let dir = dirs::config_dir()
+const DEFAULT_REFRESH_INTERVAL_SECS: u64 = 60;
+const DEFAULT_USAGE_CACHE_MAX_AGE_SECS: u64 = 24 * 60 * 60;
+const DEFAULT_TRASH_RETENTION_DAYS: u64 = 30;
+const DEFAULT_CREDENTIAL_BACKUPS: usize = 5;
+const DEFAULT_HISTORY_RETENTION_DAYS: u64 = 30;
+
+/// aip's own directory, holding `config.json` and other local state.
+pub fn aip_dir() -> Result<PathBuf> {
+    Ok(dirs::config_dir()
         .ok_or_else(|| anyhow::anyhow!("could not determine config directory"))?
-        .join("aip");
-    Ok(dir.join("config.json"))
+        .join("aip"))
+}
+
+fn config_path() -> Result<PathBuf> {
+    Ok(aip_dir()?.join("config.json"))
+}
+
+/// Parses the config file at `path`, treating a missing file as the default.
+fn read_from(path: &Path) -> Result<Config> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+    };
+    serde_json::from_str(&content).with_context(|| format!("failed to parse {}", path.display()))
 }
 
 impl Config {
+    /// Loads the config, falling back to the default (with a warning, once per
+    /// process) when it cannot be read or parsed.
     pub fn load() -> Self {
-        config_path()
-            .ok()
-            .and_then(|p| std::fs::read_to_string(p).ok())
-            .and_then(|s| serde_json::from_str(&s).ok())
-            .unwrap_or_default()
+        Self::try_load().unwrap_or_else(|e| {
+            static WARNED: Once = Once::new();
+            WARNED.call_once(|| eprintln!("Warning: using default config: {:#}", e));
+            Self::default()
+        })
+    }
+
+    /// Loads the config, failing when the file exists but cannot be parsed.
+    /// Use this before modifying and saving it.
+    pub fn try_load() -> Result<Self> {
+        read_from(&config_path()?)
+    }
+
+    /// The dashboard auto-refresh interval, or `None` when disabled.
+    pub fn refresh_interval(&self) -> Option<Duration> {
+        match self
+            .refresh_interval_secs
+            .unwrap_or(DEFAULT_REFRESH_INTERVAL_SECS)
+        {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        }
     }
 
+    /// Maximum age of cached usage shown by the dashboard, `statusline` and `usage --cached`.
+    pub fn usage_cache_max_age(&self) -> Duration {
+        Duration::from_secs(
+            self.usage_cache_max_age_secs
+                .unwrap_or(DEFAULT_USAGE_CACHE_MAX_AGE_SECS),
+        )
+    }
+
+    /// How long deleted profiles are kept, or `None` to keep them forever.
+    pub fn trash_retention(&self) -> Option<Duration> {
+        match self
+            .trash_retention_days
+            .unwrap_or(DEFAULT_TRASH_RETENTION_DAYS)
+        {
+            0 => None,
+            days => Some(Duration::from_secs(days * 24 * 60 * 60)),
+        }
+    }
+
+    /// How long usage history is kept, or `None` to keep it forever.
+    pub fn history_retention(&self) -> Option<Duration> {
+        match self
+            .history_retention_days
+            .unwrap_or(DEFAULT_HISTORY_RETENTION_DAYS)
+        {
+            0 => None,
+            days => Some(Duration::from_secs(days * 24 * 60 * 60)),
+        }
+    }
+
+    pub fn credential_backups(&self) -> usize {
+        self.credential_backups
+            .unwrap_or(DEFAULT_CREDENTIAL_BACKUPS)
+    }
+
+    pub fn home_override(&self, tool: Tool) -> Option<&Path> {
+        match tool {
+            Tool::Claude => self.claude_home.as_deref(),
+            Tool::Codex => self.codex_home.as_deref(),
+        }
+    }
+
+    /// Writes the config, refusing to replace a file that does not parse so
+    /// hand-edited settings are not lost.
     pub fn save(&self) -> Result<()> {
-        let path = config_path()?;
+        self.save_to(&config_path()?)
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        read_from(path).context("refusing to overwrite the existing config")?;
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
         let json = serde_json::to_string_pretty(self)?;
-        atomic_write(&path, &json)
+        atomic_write(path, &json)
     }
 }
 
@@ -48,10 +167,30 @@ mod tests {
         assert_eq!(config.display_mode, DisplayPreference::Default);
     }
 
+    #[test]
+    fn save_refuses_to_overwrite_unparsable_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, "{ \"display_mode\": ").unwrap();
+
+        assert!(read_from(&path).is_err());
+        assert!(Config::default().save_to(&path).is_err());
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "{ \"display_mode\": "
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(read_from(&path).is_ok());
+        Config::default().save_to(&path).unwrap();
+        assert!(read_from(&path).is_ok());
+    }
+
     #[test]
     fn round_trip_serialization() {
         let config = Config {
             display_mode: DisplayPreference::Left,
+            ..Default::default()
         };
         let json = serde_json::to_string(&config).unwrap();
         let loaded: Config = serde_json::from_str(&json).unwrap();
@@ -64,6 +203,88 @@ mod tests {
         assert_eq!(config.display_mode, DisplayPreference::Default);
     }
 
+    #[test]
+    fn refresh_interval_defaults_to_a_minute_and_zero_disables() {
+        assert_eq!(
+            Config::default().refresh_interval(),
+            Some(Duration::from_secs(60))
+        );
+
+        let config: Config = serde_json::from_str(r#"{"refresh_interval_secs": 15}"#).unwrap();
+        assert_eq!(config.refresh_interval(), Some(Duration::from_secs(15)));
+
+        let config: Config = serde_json::from_str(r#"{"refresh_interval_secs": 0}"#).unwrap();
+        assert_eq!(config.refresh_interval(), None);
+    }
+
+    #[test]
+    fn usage_cache_max_age_defaults_to_a_day() {
+        assert_eq!(
+            Config::default().usage_cache_max_age(),
+            Duration::from_secs(86400)
+        );
+
+        let config: Config = serde_json::from_str(r#"{"usage_cache_max_age_secs": 600}"#).unwrap();
+        assert_eq!(config.usage_cache_max_age(), Duration::from_secs(600));
+    }
+
+    #[test]
+    fn trash_retention_defaults_to_thirty_days_and_zero_keeps_forever() {
+        assert_eq!(
+            Config::default().trash_retention(),
+            Some(Duration::from_secs(30 * 86400))
+        );
+
+        let config: Config = serde_json::from_str(r#"{"trash_retention_days": 0}"#).unwrap();
+        assert_eq!(config.trash_retention(), None);
+    }
+
+    #[test]
+    fn credential_backups_default_to_five() {
+        assert_eq!(Config::default().credential_backups(), 5);
+
+        let config: Config = serde_json::from_str(r#"{"credential_backups": 0}"#).unwrap();
+        assert_eq!(config.credential_backups(), 0);
+    }
+
+    #[test]
+    fn auto_switch_is_off_unless_configured() {
+        assert_eq!(Config::default().auto_switch, None);
+
+        let config: Config = serde_json::from_str(r#"{"auto_switch": {"threshold": 80}}"#).unwrap();
+        assert_eq!(config.auto_switch.map(|p| p.threshold), Some(80.0));
+    }
+
+    #[test]
+    fn notification_rules_parse_from_config() {
+        let config: Config = serde_json::from_str(
+            r#"{"notifications": [
+                {"profile": "team-*", "window": "Weekly", "threshold": 80, "command": "notify-send aip"},
+                {"tool": "codex", "on_reset": true, "command": "say reset"}
+            ]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(config.notifications.len(), 2);
+        assert_eq!(config.notifications[0].threshold, Some(80.0));
+        assert!(!config.notifications[0].on_reset);
+        assert_eq!(config.notifications[1].tool.as_deref(), Some("codex"));
+        assert!(config.notifications[1].on_reset);
+    }
+
+    #[test]
+    fn home_overrides_round_trip_and_are_omitted_when_unset() {
+        let config: Config = serde_json::from_str(r#"{"codex_home": "/work/.codex"}"#).unwrap();
+        assert_eq!(
+            config.home_override(Tool::Codex),
+            Some(Path::new("/work/.codex"))
+        );
+        assert_eq!(config.home_override(Tool::Claude), None);
+
+        let json = serde_json::to_string(&Config::default()).unwrap();
+        assert!(!json.contains("home"));
+    }
+
     #[test]
     fn save_and_load_via_file() {
         let dir = tempfile::tempdir().unwrap();
@@ -73,6 +294,7 @@ mod tests {
         std::fs::create_dir_all(path.parent().unwrap()).unwrap();
         let config = Config {
             display_mode: DisplayPreference::Used,
+            ..Default::default()
         };
         let json = serde_json::to_string_pretty(&config).unwrap();
         atomic_write(&path, &json).unwrap();
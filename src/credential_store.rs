@@ -0,0 +1,407 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use anyhow::{Result, anyhow};
+
+use crate::fs_util;
+
+/// Storage for a tool's credentials.
+///
+/// The "active" credentials are the ones the tool itself reads (Keychain entry,
+/// `auth.json`, ...). Profile credentials are the copies saved under each
+/// profile directory.
+pub trait CredentialStore {
+    /// Returns `None` when the tool has no active credentials.
+    fn read_active(&self) -> Result<Option<String>>;
+
+    fn write_active(&self, data: &str) -> Result<()>;
+
+    /// Returns `None` when the profile has no saved credentials.
+    fn read_profile(&self, profile_dir: &Path) -> Result<Option<String>>;
+
+    fn write_profile(&self, profile_dir: &Path, data: &str) -> Result<()>;
+
+    /// Saves the active credentials into a profile.
+    fn save_active_to_profile(&self, profile_dir: &Path) -> Result<()> {
+        let data = self
+            .read_active()?
+            .ok_or_else(|| anyhow!("no active credentials found"))?;
+        self.write_profile(profile_dir, &data)
+    }
+}
+
+fn read_file(path: &Path) -> Result<Option<String>> {
+    let data = match fs::read_to_string(path) {
+        Ok(d) => d,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(anyhow::Error::new(e).context(format!(
+                "failed to read credentials from {}",
+                path.display()
+            )));
+        }
+    };
+    let trimmed = data.trim_end_matches('\n');
+    if trimmed.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(trimmed.to_string()))
+}
+
+fn write_file(path: &Path, data: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs_util::atomic_write_private(path, data)?;
+    Ok(())
+}
+
+/// Active credentials kept in a plain file (Codex `auth.json`, Claude Code on Linux).
+pub struct FileStore {
+    active: PathBuf,
+    profile_file: &'static str,
+}
+
+impl FileStore {
+    pub fn new(active: PathBuf, profile_file: &'static str) -> Self {
+        Self {
+            active,
+            profile_file,
+        }
+    }
+}
+
+impl CredentialStore for FileStore {
+    fn read_active(&self) -> Result<Option<String>> {
+        read_file(&self.active)
+    }
+
+    fn write_active(&self, data: &str) -> Result<()> {
+        write_file(&self.active, data)
+    }
+
+    fn read_profile(&self, profile_dir: &Path) -> Result<Option<String>> {
+        read_file(&profile_dir.join(self.profile_file))
+    }
+
+    fn write_profile(&self, profile_dir: &Path, data: &str) -> Result<()> {
+        write_file(&profile_dir.join(self.profile_file), data)
+    }
+
+    /// Copies the file as-is so that formatting of the original is preserved.
+    fn save_active_to_profile(&self, profile_dir: &Path) -> Result<()> {
+        if !self.active.exists() {
+            return Err(anyhow!("{} not found", self.active.display()));
+        }
+        let dest = profile_dir.join(self.profile_file);
+        fs_util::atomic_copy(&self.active, &dest)?;
+        Ok(())
+    }
+}
+
+/// Decode hex-encoded credentials returned by `security -w` for blob entries.
+///
+/// Claude Code stores credentials as a binary blob in Keychain.
+/// `security find-generic-password -w` returns blob data as a hex string
+/// (e.g. "7b0a2022..." for '{\n "...'), which must be decoded back to JSON.
+fn decode_hex_credentials(data: &str) -> String {
+    if data.starts_with('{') {
+        return data.to_string();
+    }
+    if !data.len().is_multiple_of(2) || !data.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return data.to_string();
+    }
+    let bytes: Vec<u8> = (0..data.len())
+        .step_by(2)
+        .filter_map(|i| u8::from_str_radix(&data[i..i + 2], 16).ok())
+        .collect();
+    match String::from_utf8(bytes) {
+        Ok(s) if s.starts_with('{') => s,
+        _ => data.to_string(),
+    }
+}
+
+fn encode_hex(data: &str) -> String {
+    data.bytes().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Active credentials kept in the macOS Keychain via the `security` command.
+pub struct KeychainStore {
+    service: &'static str,
+    profile_file: &'static str,
+}
+
+impl KeychainStore {
+    pub fn new(service: &'static str, profile_file: &'static str) -> Self {
+        Self {
+            service,
+            profile_file,
+        }
+    }
+}
+
+impl CredentialStore for KeychainStore {
+    fn read_active(&self) -> Result<Option<String>> {
+        let output = Command::new("security")
+            .args(["find-generic-password", "-s", self.service, "-w"])
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "failed to read credentials from Keychain (service: {})",
+                self.service
+            ));
+        }
+        let data = String::from_utf8(output.stdout)?;
+        let trimmed = data.trim_end_matches('\n');
+        if trimmed.is_empty() {
+            return Err(anyhow!("Keychain entry is empty"));
+        }
+        Ok(Some(decode_hex_credentials(trimmed)))
+    }
+
+    fn write_active(&self, data: &str) -> Result<()> {
+        let account = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+        let hex_data = encode_hex(data);
+
+        // Store as hex blob (-X) to match Claude Code's format.
+        // Claude Code reads Keychain with `security -w` which returns hex for blob
+        // entries, then hex-decodes before JSON parsing.
+        // -U updates an existing entry or creates a new one atomically.
+        let output = Command::new("security")
+            .args([
+                "add-generic-password",
+                "-U",
+                "-s",
+                self.service,
+                "-a",
+                &account,
+                "-X",
+                &hex_data,
+            ])
+            .output()?;
+        if !output.status.success() {
+            return Err(anyhow!(
+                "failed to write credentials to Keychain (service: {})",
+                self.service
+            ));
+        }
+        Ok(())
+    }
+
+    fn read_profile(&self, profile_dir: &Path) -> Result<Option<String>> {
+        let path = profile_dir.join(self.profile_file);
+        let Some(raw) = read_file(&path)? else {
+            return Ok(None);
+        };
+        let data = decode_hex_credentials(&raw);
+        // Profiles saved by older versions may contain the raw hex blob.
+        // Persist decoded credentials back to file if hex was decoded.
+        if data != raw
+            && let Err(e) = write_file(&path, &data)
+        {
+            eprintln!("warning: failed to update credentials format: {e}");
+        }
+        Ok(Some(data))
+    }
+
+    fn write_profile(&self, profile_dir: &Path, data: &str) -> Result<()> {
+        write_file(&profile_dir.join(self.profile_file), data)
+    }
+}
+
+/// In-memory store for exercising the shared profile logic in tests.
+///
+/// Clones share the same state, so a test can keep a handle after passing the
+/// store to a `ProfileManager`.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct MemoryStore {
+    active: std::sync::Arc<std::sync::Mutex<Option<String>>>,
+    profiles: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<PathBuf, String>>>,
+    fail_active_writes: bool,
+}
+
+#[cfg(test)]
+impl MemoryStore {
+    pub fn with_active(data: &str) -> Self {
+        let store = Self::default();
+        *store.active.lock().unwrap() = Some(data.to_string());
+        store
+    }
+
+    pub fn failing_active_writes(mut self) -> Self {
+        self.fail_active_writes = true;
+        self
+    }
+
+    pub fn active(&self) -> Option<String> {
+        self.active.lock().unwrap().clone()
+    }
+
+    pub fn profile(&self, profile_dir: &Path) -> Option<String> {
+        self.profiles.lock().unwrap().get(profile_dir).cloned()
+    }
+}
+
+#[cfg(test)]
+impl CredentialStore for MemoryStore {
+    fn read_active(&self) -> Result<Option<String>> {
+        Ok(self.active())
+    }
+
+    fn write_active(&self, data: &str) -> Result<()> {
+        if self.fail_active_writes {
+            return Err(anyhow!("simulated credential write failure"));
+        }
+        *self.active.lock().unwrap() = Some(data.to_string());
+        Ok(())
+    }
+
+    fn read_profile(&self, profile_dir: &Path) -> Result<Option<String>> {
+        Ok(self.profile(profile_dir))
+    }
+
+    fn write_profile(&self, profile_dir: &Path, data: &str) -> Result<()> {
+        self.profiles
+            .lock()
+            .unwrap()
+            .insert(profile_dir.to_path_buf(), data.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_hex_credentials_passes_through_json() {
+        let json = r#"{"claudeAiOauth":{"accessToken":"abc"}}"#;
+        assert_eq!(decode_hex_credentials(json), json);
+    }
+
+    #[test]
+    fn decode_hex_credentials_decodes_hex_encoded_json() {
+        let json = r#"{"key":"value"}"#;
+        let hex: String = json.bytes().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(decode_hex_credentials(&hex), json);
+    }
+
+    #[test]
+    fn decode_hex_credentials_passes_through_non_hex() {
+        let data = "not-hex-data!@#";
+        assert_eq!(decode_hex_credentials(data), data);
+    }
+
+    #[test]
+    fn decode_hex_credentials_passes_through_odd_length_hex() {
+        let data = "7b0";
+        assert_eq!(decode_hex_credentials(data), data);
+    }
+
+    #[test]
+    fn decode_hex_credentials_passes_through_hex_that_is_not_json() {
+        // Hex that decodes to non-JSON
+        let data = "48454c4c4f"; // "HELLO"
+        assert_eq!(decode_hex_credentials(data), data);
+    }
+
+    #[test]
+    fn encode_hex_round_trips_with_decode() {
+        let json = r#"{"claudeAiOauth":{"accessToken":"abc"}}"#;
+        let hex = encode_hex(json);
+        assert_eq!(decode_hex_credentials(&hex), json);
+    }
+
+    #[test]
+    fn encode_hex_produces_lowercase_hex() {
+        assert_eq!(encode_hex("AB"), "4142");
+    }
+
+    #[test]
+    fn file_store_round_trips_active_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(
+            dir.path().join(".claude").join(".credentials.json"),
+            "credentials.json",
+        );
+        let json = r#"{"claudeAiOauth":{"accessToken":"abc"}}"#;
+
+        store.write_active(json).unwrap();
+
+        assert_eq!(store.read_active().unwrap().as_deref(), Some(json));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_store_writes_with_0o600() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let active = dir.path().join("auth.json");
+        let store = FileStore::new(active.clone(), "auth.json");
+
+        store.write_active("{}").unwrap();
+        store.write_profile(&dir.path().join("work"), "{}").unwrap();
+
+        for path in [active, dir.path().join("work").join("auth.json")] {
+            let mode = fs::metadata(&path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode, 0o600, "credential file should be owner-only (0o600)");
+        }
+    }
+
+    #[test]
+    fn file_store_treats_missing_or_empty_file_as_none() {
+        let dir = tempfile::tempdir().unwrap();
+        let active = dir.path().join("auth.json");
+        let store = FileStore::new(active.clone(), "auth.json");
+
+        assert!(store.read_active().unwrap().is_none());
+        fs::write(&active, "\n").unwrap();
+        assert!(store.read_active().unwrap().is_none());
+        assert!(store.read_profile(dir.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn file_store_save_active_to_profile_copies_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let active = dir.path().join("auth.json");
+        let profile_dir = dir.path().join("profiles").join("work");
+        fs::create_dir_all(&profile_dir).unwrap();
+        fs::write(&active, "{\"tokens\":{}}\n").unwrap();
+        let store = FileStore::new(active, "auth.json");
+
+        store.save_active_to_profile(&profile_dir).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(profile_dir.join("auth.json")).unwrap(),
+            "{\"tokens\":{}}\n"
+        );
+    }
+
+    #[test]
+    fn file_store_save_active_to_profile_fails_without_active_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path().join("auth.json"), "auth.json");
+
+        assert!(store.save_active_to_profile(dir.path()).is_err());
+    }
+
+    #[test]
+    fn keychain_store_decodes_and_persists_hex_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = r#"{"claudeAiOauth":{"accessToken":"abc"}}"#;
+        fs::write(dir.path().join("credentials.json"), encode_hex(json)).unwrap();
+        let store = KeychainStore::new("unused", "credentials.json");
+
+        assert_eq!(
+            store.read_profile(dir.path()).unwrap().as_deref(),
+            Some(json)
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("credentials.json")).unwrap(),
+            json
+        );
+    }
+}
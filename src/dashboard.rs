@@ -1,37 +1,30 @@
 use std::collections::{HashMap, HashSet};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use chrono::Local;
+use chrono::{DateTime, Local, TimeDelta, Utc};
 use console::{Key, Term};
 
-use crate::claude;
-use crate::claude::usage::RateLimitError;
-use crate::codex;
-use crate::codex::usage::RateLimits;
+use crate::auto_switch;
 use crate::config::Config;
-use crate::display::{DisplayMode, DisplayPreference, format_usage_line};
+use crate::display::{
+    DisplayMode, DisplayPreference, abbreviate_home, capitalize_first, format_usage_line,
+};
+use crate::forecast::BurnTracker;
+use crate::history;
+use crate::meta::ProfileMeta;
+use crate::notify::{self, Notifier};
+use crate::profile::{self, SwitchError};
 use crate::tool::Tool;
+use crate::trash;
+use crate::usage::{
+    UsageCache, UsageLine, merge_usage_cache, prefetch_claude_usage, prefetch_codex_usage,
+    rate_limited_until,
+};
+use crate::usage_cache::StoredUsage;
 
 const SPINNER_FRAMES: &[&str] = &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
 
-#[derive(Clone, Debug, PartialEq)]
-enum UsageLine {
-    Data {
-        label: String,
-        percent: f64,
-        resets_at: Option<chrono::DateTime<chrono::Utc>>,
-    },
-    Text(String),
-}
-
-#[derive(Clone, Debug, PartialEq)]
-struct ProfileUsageCache {
-    usage_lines: Vec<UsageLine>,
-    plan_type: Option<String>,
-    is_stale: bool,
-}
-
 fn default_display_mode(tool: &Tool) -> DisplayMode {
     match tool {
         Tool::Claude => DisplayMode::Used,
@@ -39,8 +32,6 @@ fn default_display_mode(tool: &Tool) -> DisplayMode {
     }
 }
 
-type UsageCache = HashMap<String, ProfileUsageCache>;
-
 enum DashboardMode {
     Normal,
     DeleteConfirm(usize),
@@ -50,7 +41,9 @@ enum DashboardAction {
     None,
     Render,
     Refresh,
-    RefreshAfterDelete,
+    /// Carries the deleted profile's tool and trash entry id.
+    RefreshAfterDelete(Tool, String),
+    Undo,
     Switch(Tool, String),
     Quit,
 }
@@ -85,188 +78,12 @@ fn spawn_key_reader() -> tokio::sync::mpsc::UnboundedReceiver<std::io::Result<Ke
     rx
 }
 
-fn capitalize_first(s: &str) -> String {
-    let mut chars = s.chars();
-    match chars.next() {
-        None => String::new(),
-        Some(c) => c.to_uppercase().to_string() + chars.as_str(),
-    }
-}
-
-// --- Usage fetching ---
-
-fn format_retry_after(d: Duration) -> String {
-    let secs = d.as_secs();
-    if secs >= 60 {
-        format!("Rate limited (resets in {}m {}s)", secs / 60, secs % 60)
-    } else if secs > 0 {
-        format!("Rate limited (resets in {}s)", secs)
-    } else {
-        "Rate limited".to_string()
-    }
-}
-
-async fn prefetch_claude_usage() -> UsageCache {
-    let results = claude::usage::fetch_all_profiles_usage().await;
-
-    results
-        .into_iter()
-        .map(|(profile, result)| {
-            let entry = match result {
-                Ok((usage, info)) => ProfileUsageCache {
-                    usage_lines: vec![
-                        UsageLine::Data {
-                            label: "5-hour".to_string(),
-                            percent: usage.five_hour.utilization,
-                            resets_at: usage.five_hour.resets_at,
-                        },
-                        UsageLine::Data {
-                            label: "Weekly".to_string(),
-                            percent: usage.seven_day.utilization,
-                            resets_at: usage.seven_day.resets_at,
-                        },
-                    ],
-                    plan_type: info.plan_type,
-                    is_stale: false,
-                },
-                Err(e) => {
-                    if let Some(rate_err) = e.downcast_ref::<RateLimitError>() {
-                        let retry = rate_err.retry_after;
-                        ProfileUsageCache {
-                            usage_lines: vec![UsageLine::Text(format_retry_after(retry))],
-                            plan_type: None,
-                            // retry-after:0 may indicate unsupported plan;
-                            // use stale cache to preserve previous usage data.
-                            is_stale: retry.is_zero(),
-                        }
-                    } else {
-                        ProfileUsageCache {
-                            usage_lines: vec![UsageLine::Text(format!("Error: {}", e))],
-                            plan_type: None,
-                            is_stale: true,
-                        }
-                    }
-                }
-            };
-            (profile, entry)
-        })
-        .collect()
-}
-
-/// Merge new usage cache with old cache.
-///
-/// When a new entry is stale and old entry has valid (non-stale) data,
-/// keep the old data but mark it as stale so the UI can show "(stale)".
-fn merge_usage_cache(new_cache: UsageCache, old_cache: Option<&UsageCache>) -> UsageCache {
-    let old = match old_cache {
-        Some(c) => c,
-        None => return new_cache,
-    };
-    new_cache
-        .into_iter()
-        .map(|(profile, new_entry)| {
-            if new_entry.is_stale
-                && let Some(old_entry) = old.get(&profile)
-                && !old_entry.is_stale
-            {
-                return (
-                    profile,
-                    ProfileUsageCache {
-                        is_stale: true,
-                        ..old_entry.clone()
-                    },
-                );
-            }
-            (profile, new_entry)
-        })
-        .collect()
-}
-
-fn codex_usage_result(result: Result<Option<RateLimits>>) -> (Vec<UsageLine>, bool) {
-    match result {
-        Ok(Some(limits)) => {
-            let mut lines = Vec::new();
-            if let Some(primary) = &limits.primary {
-                lines.push(UsageLine::Data {
-                    label: "5-hour".to_string(),
-                    percent: primary.used_percent,
-                    resets_at: primary.resets_at_utc(),
-                });
-            }
-            if let Some(secondary) = &limits.secondary {
-                lines.push(UsageLine::Data {
-                    label: "Weekly".to_string(),
-                    percent: secondary.used_percent,
-                    resets_at: secondary.resets_at_utc(),
-                });
-            }
-            if lines.is_empty() {
-                (
-                    vec![UsageLine::Text("No usage data available".to_string())],
-                    false,
-                )
-            } else {
-                (lines, false)
-            }
-        }
-        Ok(None) => (
-            vec![UsageLine::Text("No usage data available".to_string())],
-            false,
-        ),
-        Err(e) => (vec![UsageLine::Text(format!("Error: {}", e))], true),
-    }
-}
-
-async fn prefetch_codex_usage(profiles: &[String]) -> UsageCache {
-    // Sync active auth.json to current profile before fetching usage,
-    // analogous to sync_keychain_to_current_profile for Claude.
-    let _ = tokio::task::spawn_blocking(codex::profile::sync_auth_to_current_profile).await;
-
-    let current = Tool::Codex.current_profile().ok().flatten();
+// --- Dashboard ---
 
-    let mut handles = Vec::new();
-    for p in profiles {
-        let p = p.clone();
-        let is_current = current.as_deref() == Some(p.as_str());
-        handles.push(tokio::spawn(async move {
-            let result = if is_current {
-                codex::usage::fetch_usage().await
-            } else {
-                async {
-                    let dir = Tool::Codex.profile_dir(&p)?;
-                    let auth_path = dir.join("auth.json");
-                    codex::usage::fetch_usage_from_auth(&auth_path).await
-                }
-                .await
-            };
-            let (usage_lines, is_stale) = codex_usage_result(result);
-            (
-                p,
-                ProfileUsageCache {
-                    usage_lines,
-                    plan_type: None,
-                    is_stale,
-                },
-            )
-        }));
-    }
-
-    let mut results = HashMap::new();
-    for handle in handles {
-        match handle.await {
-            Ok((p, entry)) => {
-                results.insert(p, entry);
-            }
-            Err(join_err) => {
-                eprintln!("prefetch_codex_usage task panicked: {}", join_err);
-            }
-        }
-    }
-    results
+fn is_rate_limited(usage_caches: &HashMap<Tool, UsageCache>) -> bool {
+    rate_limited_until(usage_caches.values(), Utc::now()).is_some()
 }
 
-// --- Dashboard ---
-
 fn load_tool_profiles() -> Vec<(Tool, Vec<String>, Option<String>)> {
     Tool::ALL
         .iter()
@@ -278,12 +95,17 @@ fn load_tool_profiles() -> Vec<(Tool, Vec<String>, Option<String>)> {
         .collect()
 }
 
-fn get_codex_profiles(tool_profiles: &[(Tool, Vec<String>, Option<String>)]) -> &[String] {
+fn load_profile_metas(
+    tool_profiles: &[(Tool, Vec<String>, Option<String>)],
+) -> HashMap<(Tool, String), ProfileMeta> {
     tool_profiles
         .iter()
-        .find(|(t, _, _)| *t == Tool::Codex)
-        .map(|(_, p, _)| p.as_slice())
-        .unwrap_or_default()
+        .flat_map(|(tool, profiles, _)| profiles.iter().map(move |p| (*tool, p)))
+        .filter_map(|(tool, p)| {
+            let dir = tool.profile_dir(p).ok()?;
+            Some(((tool, p.clone()), ProfileMeta::load_for_display(tool, &dir)))
+        })
+        .collect()
 }
 
 fn build_selectable_items(
@@ -307,8 +129,32 @@ fn is_current_profile(
         == Some(profile)
 }
 
+/// Auto-refresh state shown in the header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AutoRefresh {
+    Off,
+    In(Duration),
+    /// Waiting for a rate-limit retry-after window to pass.
+    Paused,
+}
+
+fn auto_refresh_status(
+    next_refresh: Option<Instant>,
+    rate_limited: bool,
+    now: Instant,
+) -> AutoRefresh {
+    match next_refresh {
+        None => AutoRefresh::Off,
+        Some(_) if rate_limited => AutoRefresh::Paused,
+        Some(at) => AutoRefresh::In(at.saturating_duration_since(now)),
+    }
+}
+
 struct DashboardView<'a> {
     tool_profiles: &'a [(Tool, Vec<String>, Option<String>)],
+    home_dirs: &'a HashMap<Tool, String>,
+    profile_metas: &'a HashMap<(Tool, String), ProfileMeta>,
+    burn: &'a BurnTracker,
     usage_caches: &'a HashMap<Tool, UsageCache>,
     pending_tools: &'a HashSet<Tool>,
     selectable_items: &'a [(Tool, String)],
@@ -317,6 +163,8 @@ struct DashboardView<'a> {
     spinner_frame: usize,
     status_message: Option<&'a str>,
     display_preference: DisplayPreference,
+    updated_at: DateTime<Local>,
+    auto_refresh: AutoRefresh,
 }
 
 impl DashboardView<'_> {
@@ -324,8 +172,15 @@ impl DashboardView<'_> {
         let mut lines = Vec::new();
 
         let header = if self.pending_tools.is_empty() {
-            let timestamp = Local::now().format("%H:%M:%S");
-            format!("aip - Usage Monitor  Updated: {}", timestamp)
+            let timestamp = self.updated_at.format("%H:%M:%S");
+            let refresh = match self.auto_refresh {
+                AutoRefresh::Off => String::new(),
+                AutoRefresh::In(remaining) => {
+                    format!("  Next refresh in {}s", remaining.as_secs())
+                }
+                AutoRefresh::Paused => "  Auto-refresh paused (rate limited)".to_string(),
+            };
+            format!("aip - Usage Monitor  Updated: {}{}", timestamp, refresh)
         } else {
             "aip - Usage Monitor  Refreshing...".to_string()
         };
@@ -335,7 +190,10 @@ impl DashboardView<'_> {
         let mut item_idx = 0;
 
         for (tool, profiles, current) in self.tool_profiles {
-            lines.push(tool.to_string());
+            match self.home_dirs.get(tool) {
+                Some(home) => lines.push(format!("{}  \x1b[2m{}\x1b[0m", tool, home)),
+                None => lines.push(tool.to_string()),
+            }
 
             if profiles.is_empty() {
                 lines.push("  (no profiles)".to_string());
@@ -355,6 +213,12 @@ impl DashboardView<'_> {
                         .and_then(|e| e.plan_type.as_deref())
                         .map(|pt| format!(" ({})", capitalize_first(pt)))
                         .unwrap_or_default();
+                    let meta_suffix = self
+                        .profile_metas
+                        .get(&(*tool, profile.clone()))
+                        .and_then(ProfileMeta::summary)
+                        .map(|summary| format!("  \x1b[2m{}\x1b[0m", summary))
+                        .unwrap_or_default();
                     let stale_suffix = if entry.is_some_and(|e| e.is_stale) {
                         " \x1b[2m(stale)\x1b[0m"
                     } else {
@@ -367,8 +231,14 @@ impl DashboardView<'_> {
                         String::new()
                     };
                     let line = format!(
-                        "{} {}{}{}{}{}",
-                        cursor, profile, marker, plan_suffix, stale_suffix, spinner_suffix
+                        "{} {}{}{}{}{}{}",
+                        cursor,
+                        profile,
+                        marker,
+                        plan_suffix,
+                        meta_suffix,
+                        stale_suffix,
+                        spinner_suffix
                     );
                     if is_selected {
                         lines.push(format!("\x1b[1;36m{}\x1b[0m", line));
@@ -384,7 +254,16 @@ impl DashboardView<'_> {
                                     label,
                                     percent,
                                     resets_at,
-                                } => format_usage_line(label, *percent, *resets_at, &mode),
+                                } => {
+                                    let line =
+                                        format_usage_line(label, *percent, *resets_at, &mode);
+                                    match self.burn.forecast(*tool, profile, label) {
+                                        Some(forecast) => {
+                                            format!("{}  {}", line, forecast.label())
+                                        }
+                                        None => line,
+                                    }
+                                }
                                 UsageLine::Text(text) => text.clone(),
                             };
                             lines.push(format!("    {}", formatted));
@@ -408,7 +287,7 @@ impl DashboardView<'_> {
         match self.mode {
             DashboardMode::Normal => {
                 lines.push(
-                    "[R] Refresh  [D] Display  [↑↓] Navigate  [Enter/Space] Switch  [BS/Del] Delete  [Shift+J/K] Reorder  [ESC/q] Quit"
+                    "[R] Refresh  [D] Display  [↑↓] Navigate  [Enter/Space] Switch  [BS/Del] Delete  [U] Undo  [Shift+J/K] Reorder  [ESC/q] Quit"
                         .to_string(),
                 );
             }
@@ -490,13 +369,6 @@ fn handle_move(
     }
 }
 
-fn switch_profile(tool: Tool, profile: &str) -> Result<()> {
-    match tool {
-        Tool::Claude => claude::profile::switch(profile),
-        Tool::Codex => codex::profile::switch(profile),
-    }
-}
-
 fn normalize_key(key: Key) -> Key {
     match key {
         Key::Char(c) => Key::Char(normalize_command_char(c)),
@@ -567,6 +439,7 @@ fn handle_dashboard_key(
                 *display_preference = display_preference.next();
                 DashboardAction::Render
             }
+            Key::Char('u') => DashboardAction::Undo,
             Key::Char('K') => handle_move(selected, selectable_items, tool_profiles, -1),
             Key::Char('J') => handle_move(selected, selectable_items, tool_profiles, 1),
             Key::Escape | Key::Char('q') => DashboardAction::Quit,
@@ -579,7 +452,7 @@ fn handle_dashboard_key(
                     *mode = DashboardMode::Normal;
                     match selectable_items.get(idx) {
                         Some((tool, profile)) => match tool.delete_profile(profile) {
-                            Ok(()) => DashboardAction::RefreshAfterDelete,
+                            Ok(id) => DashboardAction::RefreshAfterDelete(*tool, id),
                             Err(e) => {
                                 *status_message = Some(format!("Failed to delete profile: {}", e));
                                 DashboardAction::Render
@@ -602,6 +475,9 @@ fn handle_dashboard_key(
 fn render_dashboard(
     term: &Term,
     tool_profiles: &[(Tool, Vec<String>, Option<String>)],
+    home_dirs: &HashMap<Tool, String>,
+    profile_metas: &HashMap<(Tool, String), ProfileMeta>,
+    burn: &BurnTracker,
     usage_caches: &HashMap<Tool, UsageCache>,
     pending_tools: &HashSet<Tool>,
     selectable_items: &[(Tool, String)],
@@ -610,9 +486,14 @@ fn render_dashboard(
     spinner_frame: usize,
     status_message: Option<&str>,
     display_preference: DisplayPreference,
+    updated_at: DateTime<Local>,
+    auto_refresh: AutoRefresh,
 ) -> Result<()> {
     DashboardView {
         tool_profiles,
+        home_dirs,
+        profile_metas,
+        burn,
         usage_caches,
         pending_tools,
         selectable_items,
@@ -621,6 +502,8 @@ fn render_dashboard(
         spinner_frame,
         status_message,
         display_preference,
+        updated_at,
+        auto_refresh,
     }
     .render(term)
 }
@@ -631,35 +514,67 @@ pub async fn cmd_dashboard() -> Result<()> {
     let _guard = ScreenGuard(&term);
     term.hide_cursor()?;
 
-    let mut usage_caches: HashMap<Tool, UsageCache> = HashMap::new();
+    let config = Config::load();
+    let home = dirs::home_dir();
+    let home_dirs: HashMap<Tool, String> = Tool::ALL
+        .iter()
+        .filter_map(|&t| Some((t, abbreviate_home(&t.home_dir().ok()?, home.as_deref()))))
+        .collect();
+    // Start from the on-disk cache, marked stale until the first fetch lands.
+    let stored_usage = StoredUsage::load();
+    let mut usage_caches: HashMap<Tool, UsageCache> = Tool::ALL
+        .into_iter()
+        .map(|tool| {
+            let cache =
+                stored_usage.usage_cache(tool, config.usage_cache_max_age(), Utc::now(), true);
+            (tool, cache)
+        })
+        .collect();
+    let mut burn = BurnTracker::default();
+    for sample in history::load(None, None, Utc::now() - TimeDelta::days(7)).unwrap_or_default() {
+        burn.add(&sample);
+    }
     let mut key_rx = spawn_key_reader();
     let mut selected: usize = 0;
     let mut mode = DashboardMode::Normal;
-    let mut display_preference = Config::load().display_mode;
+    let mut display_preference = config.display_mode;
+    let refresh_interval = config.refresh_interval();
+    let auto_switch_policy = config.auto_switch.clone();
+    let mut notifier = Notifier::from_config(&config);
+    let mut notice: Option<String> = None;
+    let mut last_deleted: Option<(Tool, String)> = None;
     let mut spinner_frame: usize = 0;
     let mut spinner_interval = tokio::time::interval(Duration::from_millis(80));
+    let mut refresh_tick = tokio::time::interval(Duration::from_secs(1));
+    let mut updated_at = Local::now();
     let mut status_message: Option<String>;
     let ctrl_c_future = tokio::signal::ctrl_c();
     tokio::pin!(ctrl_c_future);
 
     loop {
-        status_message = None;
+        status_message = notice.take();
         let tool_profiles = load_tool_profiles();
-        let codex_profiles = get_codex_profiles(&tool_profiles);
+        let profile_metas = load_profile_metas(&tool_profiles);
+        let claude_profiles = tool_profiles_for(Tool::Claude, &tool_profiles);
+        let codex_profiles = tool_profiles_for(Tool::Codex, &tool_profiles);
         let selectable_items = build_selectable_items(&tool_profiles);
 
         selected = selected.min(selectable_items.len().saturating_sub(1));
 
-        let claude_future = prefetch_claude_usage();
-        let codex_future = prefetch_codex_usage(codex_profiles);
+        let claude_future = prefetch_claude_usage(&claude_profiles);
+        let codex_future = prefetch_codex_usage(&codex_profiles);
         tokio::pin!(claude_future);
         tokio::pin!(codex_future);
 
         let mut pending_tools: HashSet<Tool> = HashSet::from([Tool::Claude, Tool::Codex]);
+        let mut next_refresh: Option<Instant> = None;
 
         render_dashboard(
             &term,
             &tool_profiles,
+            &home_dirs,
+            &profile_metas,
+            &burn,
             &usage_caches,
             &pending_tools,
             &selectable_items,
@@ -668,28 +583,53 @@ pub async fn cmd_dashboard() -> Result<()> {
             spinner_frame,
             status_message.as_deref(),
             display_preference,
+            updated_at,
+            AutoRefresh::Off,
         )?;
 
         loop {
             let mut should_render = false;
+            let mut fetched = false;
 
             tokio::select! {
                 cache = &mut claude_future, if pending_tools.contains(&Tool::Claude) => {
+                    if let Some(notifier) = &mut notifier {
+                        notify::spawn_hooks(notifier.evaluate(Tool::Claude, &cache, Utc::now()));
+                    }
+                    for sample in history::samples_from_cache(Tool::Claude, &cache, Utc::now()) {
+                        burn.add(&sample);
+                    }
                     let merged = merge_usage_cache(cache, usage_caches.get(&Tool::Claude));
                     usage_caches.insert(Tool::Claude, merged);
                     pending_tools.remove(&Tool::Claude);
+                    fetched = true;
                     should_render = true;
                 }
                 cache = &mut codex_future, if pending_tools.contains(&Tool::Codex) => {
+                    if let Some(notifier) = &mut notifier {
+                        notify::spawn_hooks(notifier.evaluate(Tool::Codex, &cache, Utc::now()));
+                    }
+                    for sample in history::samples_from_cache(Tool::Codex, &cache, Utc::now()) {
+                        burn.add(&sample);
+                    }
                     let merged = merge_usage_cache(cache, usage_caches.get(&Tool::Codex));
                     usage_caches.insert(Tool::Codex, merged);
                     pending_tools.remove(&Tool::Codex);
+                    fetched = true;
                     should_render = true;
                 }
                 _ = spinner_interval.tick(), if !pending_tools.is_empty() => {
                     spinner_frame = spinner_frame.wrapping_add(1);
                     should_render = true;
                 }
+                _ = refresh_tick.tick(), if pending_tools.is_empty() && next_refresh.is_some() => {
+                    if !is_rate_limited(&usage_caches)
+                        && next_refresh.is_some_and(|at| Instant::now() >= at)
+                    {
+                        break;
+                    }
+                    should_render = true;
+                }
                 _ = &mut ctrl_c_future => {
                     return Ok(());
                 }
@@ -711,51 +651,40 @@ pub async fn cmd_dashboard() -> Result<()> {
                     ) {
                         DashboardAction::Quit => return Ok(()),
                         DashboardAction::Refresh => break,
-                        DashboardAction::RefreshAfterDelete => {
+                        DashboardAction::RefreshAfterDelete(tool, id) => {
+                            last_deleted = Some((tool, id));
                             selected = selected.saturating_sub(1);
                             break;
                         }
-                        DashboardAction::Switch(tool, ref profile) => {
-                            if tool == Tool::Claude
-                                && let Ok(dir) = Tool::Claude.profile_dir(profile)
-                            {
-                                // Refresh only non-current profiles before switching.
-                                // The current profile's token is managed by Claude Code.
-                                let current =
-                                    Tool::Claude.current_profile().ok().flatten();
-                                if current.as_deref() != Some(profile.as_str())
-                                    && let Err(e) =
-                                        claude::usage::refresh_credentials_if_expired(
-                                            &dir.join("credentials.json"),
-                                        )
-                                        .await
-                                    {
-                                        status_message = Some(format!(
-                                            "Token refresh failed for '{}': {}",
-                                            profile, e
-                                        ));
-                                        continue;
-                                    }
-                            }
-                            let profile = profile.clone();
-                            match tokio::task::spawn_blocking(move || {
-                                switch_profile(tool, &profile)
-                            })
-                            .await
-                            {
-                                Ok(Ok(())) => break,
-                                Ok(Err(e)) => {
-                                    status_message = Some(format!(
-                                        "Failed to switch profile: {}",
-                                        e,
-                                    ));
+                        DashboardAction::Undo => match last_deleted.take() {
+                            Some((tool, id)) => match trash::restore(tool, &id) {
+                                Ok(entry) => {
+                                    notice = Some(format!("Restored '{}' for {}", entry.profile, tool));
+                                    break;
+                                }
+                                Err(e) => {
+                                    status_message = Some(format!("Failed to restore profile: {}", e));
                                     should_render = true;
                                 }
-                                Err(join_err) => {
-                                    status_message = Some(format!(
-                                        "Failed to switch profile: {}",
-                                        join_err,
-                                    ));
+                            },
+                            None => {
+                                status_message = Some("Nothing to undo".to_string());
+                                should_render = true;
+                            }
+                        },
+                        DashboardAction::Switch(tool, ref profile) => {
+                            match profile::switch(tool, profile).await {
+                                Ok(()) => break,
+                                Err(e) => {
+                                    status_message = Some(match e.downcast_ref::<SwitchError>() {
+                                        Some(SwitchError::RefreshFailed { profile, source }) => {
+                                            format!(
+                                                "Token refresh failed for '{}': {}",
+                                                profile, source
+                                            )
+                                        }
+                                        _ => format!("Failed to switch profile: {}", e),
+                                    });
                                     should_render = true;
                                 }
                             }
@@ -764,15 +693,58 @@ pub async fn cmd_dashboard() -> Result<()> {
                         DashboardAction::None => {}
                     }
                     if display_preference != prev_display_preference {
-                        let _ = Config { display_mode: display_preference }.save();
+                        let saved = Config::try_load().and_then(|mut config| {
+                            config.display_mode = display_preference;
+                            config.save()
+                        });
+                        if let Err(e) = saved {
+                            notice = Some(format!("Display mode not saved: {:#}", e));
+                        }
+                    }
+                }
+            }
+
+            if fetched && pending_tools.is_empty() {
+                updated_at = Local::now();
+                next_refresh = refresh_interval.map(|interval| Instant::now() + interval);
+
+                if let Some(policy) = &auto_switch_policy {
+                    let mut switched = false;
+                    for (tool, cache) in &usage_caches {
+                        match auto_switch::run(policy, *tool, cache).await {
+                            Ok(Some(decision)) => {
+                                notice = Some(format!(
+                                    "Auto-switched {} to '{}': {}",
+                                    tool,
+                                    decision.to,
+                                    decision.reason()
+                                ));
+                                switched = true;
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                status_message = Some(format!("Auto-switch failed: {}", e));
+                            }
+                        }
+                    }
+                    if switched {
+                        break;
                     }
                 }
             }
 
             if should_render {
+                let auto_refresh = auto_refresh_status(
+                    next_refresh,
+                    is_rate_limited(&usage_caches),
+                    Instant::now(),
+                );
                 render_dashboard(
                     &term,
                     &tool_profiles,
+                    &home_dirs,
+                    &profile_metas,
+                    &burn,
                     &usage_caches,
                     &pending_tools,
                     &selectable_items,
@@ -781,6 +753,8 @@ pub async fn cmd_dashboard() -> Result<()> {
                     spinner_frame,
                     status_message.as_deref(),
                     display_preference,
+                    updated_at,
+                    auto_refresh,
                 )?;
             }
         }
@@ -790,7 +764,7 @@ pub async fn cmd_dashboard() -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::codex::usage::RateWindow;
+    use crate::usage::ProfileUsageCache;
 
     fn build_lines(
         tool_profiles: &[(Tool, Vec<String>, Option<String>)],
@@ -803,6 +777,9 @@ mod tests {
     ) -> Vec<String> {
         DashboardView {
             tool_profiles,
+            home_dirs: &HashMap::new(),
+            profile_metas: &HashMap::new(),
+            burn: &BurnTracker::default(),
             usage_caches,
             pending_tools,
             selectable_items,
@@ -811,6 +788,8 @@ mod tests {
             spinner_frame,
             status_message: None,
             display_preference: DisplayPreference::Default,
+            updated_at: Local::now(),
+            auto_refresh: AutoRefresh::Off,
         }
         .build_lines()
     }
@@ -820,6 +799,7 @@ mod tests {
             usage_lines,
             plan_type: plan_type.map(String::from),
             is_stale: false,
+            rate_limited_until: None,
         }
     }
 
@@ -1131,6 +1111,56 @@ mod tests {
         assert!(!lines[0].contains("Refreshing..."));
     }
 
+    fn header_with(auto_refresh: AutoRefresh) -> String {
+        DashboardView {
+            tool_profiles: &[],
+            home_dirs: &HashMap::new(),
+            profile_metas: &HashMap::new(),
+            burn: &BurnTracker::default(),
+            usage_caches: &HashMap::new(),
+            pending_tools: &HashSet::new(),
+            selectable_items: &[],
+            selected: 0,
+            mode: &DashboardMode::Normal,
+            spinner_frame: 0,
+            status_message: None,
+            display_preference: DisplayPreference::Default,
+            updated_at: Local::now(),
+            auto_refresh,
+        }
+        .build_lines()
+        .remove(0)
+    }
+
+    #[test]
+    fn build_dashboard_lines_header_shows_refresh_countdown() {
+        assert!(
+            header_with(AutoRefresh::In(Duration::from_secs(42))).contains("Next refresh in 42s")
+        );
+        assert!(header_with(AutoRefresh::Paused).contains("Auto-refresh paused"));
+        assert!(!header_with(AutoRefresh::Off).contains("refresh"));
+    }
+
+    #[test]
+    fn auto_refresh_status_counts_down_and_pauses_when_rate_limited() {
+        let now = Instant::now();
+        let next = now + Duration::from_secs(30);
+
+        assert_eq!(auto_refresh_status(None, false, now), AutoRefresh::Off);
+        assert_eq!(
+            auto_refresh_status(Some(next), false, now),
+            AutoRefresh::In(Duration::from_secs(30))
+        );
+        assert_eq!(
+            auto_refresh_status(Some(next), true, now),
+            AutoRefresh::Paused
+        );
+        assert_eq!(
+            auto_refresh_status(Some(now), false, next),
+            AutoRefresh::In(Duration::ZERO)
+        );
+    }
+
     #[test]
     fn build_dashboard_lines_shows_plan_type() {
         let tool_profiles = vec![(
@@ -1249,14 +1279,6 @@ mod tests {
         assert!(footer.contains("Delete 'work' for Claude Code? [y/n]"));
     }
 
-    #[test]
-    fn capitalize_first_capitalizes_first_char() {
-        assert_eq!(capitalize_first("pro"), "Pro");
-        assert_eq!(capitalize_first(""), "");
-        assert_eq!(capitalize_first("Pro"), "Pro");
-        assert_eq!(capitalize_first("日本語"), "日本語");
-    }
-
     #[test]
     fn normalize_command_char_converts_full_width_ascii() {
         assert_eq!(normalize_command_char('ｑ'), 'q');
@@ -1663,6 +1685,25 @@ mod tests {
         assert!(matches!(action, DashboardAction::Refresh));
     }
 
+    #[test]
+    fn handle_dashboard_key_u_undoes() {
+        let tool_profiles = sample_tool_profiles();
+        let selectable_items = build_selectable_items(&tool_profiles);
+        let mut selected = 0;
+        let mut mode = DashboardMode::Normal;
+
+        let action = handle_dashboard_key(
+            Key::Char('u'),
+            &mut selected,
+            &mut mode,
+            &selectable_items,
+            &tool_profiles,
+            &mut None,
+            &mut DisplayPreference::Default,
+        );
+        assert!(matches!(action, DashboardAction::Undo));
+    }
+
     #[test]
     fn handle_dashboard_key_quits_when_no_selectable_items() {
         let tool_profiles = vec![(Tool::Claude, vec![], None)];
@@ -1802,6 +1843,9 @@ mod tests {
         // Default for Claude is Used
         let lines_used = DashboardView {
             tool_profiles: &tool_profiles,
+            home_dirs: &HashMap::new(),
+            profile_metas: &HashMap::new(),
+            burn: &BurnTracker::default(),
             usage_caches: &usage_caches,
             pending_tools: &HashSet::new(),
             selectable_items: &selectable_items,
@@ -1810,6 +1854,8 @@ mod tests {
             spinner_frame: 0,
             status_message: None,
             display_preference: DisplayPreference::Default,
+            updated_at: Local::now(),
+            auto_refresh: AutoRefresh::Off,
         }
         .build_lines();
         assert!(
@@ -1821,6 +1867,9 @@ mod tests {
         // Override to Left
         let lines_left = DashboardView {
             tool_profiles: &tool_profiles,
+            home_dirs: &HashMap::new(),
+            profile_metas: &HashMap::new(),
+            burn: &BurnTracker::default(),
             usage_caches: &usage_caches,
             pending_tools: &HashSet::new(),
             selectable_items: &selectable_items,
@@ -1829,6 +1878,8 @@ mod tests {
             spinner_frame: 0,
             status_message: None,
             display_preference: DisplayPreference::Left,
+            updated_at: Local::now(),
+            auto_refresh: AutoRefresh::Off,
         }
         .build_lines();
         assert!(
@@ -1838,147 +1889,6 @@ mod tests {
         );
     }
 
-    #[test]
-    fn merge_usage_cache_keeps_old_data_when_new_is_stale() {
-        let old: UsageCache = HashMap::from([(
-            "main".to_string(),
-            ProfileUsageCache {
-                usage_lines: vec![UsageLine::Text("5-hour  40.0% used".to_string())],
-                plan_type: Some("pro".to_string()),
-                is_stale: false,
-            },
-        )]);
-        let new: UsageCache = HashMap::from([(
-            "main".to_string(),
-            ProfileUsageCache {
-                usage_lines: vec![UsageLine::Text("Rate limited".to_string())],
-                plan_type: None,
-                is_stale: true,
-            },
-        )]);
-
-        let merged = merge_usage_cache(new, Some(&old));
-        let entry = &merged["main"];
-        assert!(entry.is_stale);
-        assert_eq!(
-            entry.usage_lines,
-            vec![UsageLine::Text("5-hour  40.0% used".to_string())]
-        );
-        assert_eq!(entry.plan_type, Some("pro".to_string()));
-    }
-
-    #[test]
-    fn merge_usage_cache_uses_new_data_when_not_stale() {
-        let old: UsageCache = HashMap::from([(
-            "main".to_string(),
-            ProfileUsageCache {
-                usage_lines: vec![UsageLine::Text("5-hour  40.0% used".to_string())],
-                plan_type: Some("pro".to_string()),
-                is_stale: false,
-            },
-        )]);
-        let new: UsageCache = HashMap::from([(
-            "main".to_string(),
-            ProfileUsageCache {
-                usage_lines: vec![UsageLine::Text("5-hour  50.0% used".to_string())],
-                plan_type: Some("pro".to_string()),
-                is_stale: false,
-            },
-        )]);
-
-        let merged = merge_usage_cache(new, Some(&old));
-        let entry = &merged["main"];
-        assert!(!entry.is_stale);
-        assert_eq!(
-            entry.usage_lines,
-            vec![UsageLine::Text("5-hour  50.0% used".to_string())]
-        );
-    }
-
-    #[test]
-    fn merge_usage_cache_uses_fallback_when_no_old_data() {
-        let new: UsageCache = HashMap::from([(
-            "main".to_string(),
-            ProfileUsageCache {
-                usage_lines: vec![UsageLine::Text("Rate limited".to_string())],
-                plan_type: None,
-                is_stale: true,
-            },
-        )]);
-
-        let merged = merge_usage_cache(new, None);
-        let entry = &merged["main"];
-        assert!(entry.is_stale);
-        assert_eq!(
-            entry.usage_lines,
-            vec![UsageLine::Text("Rate limited".to_string())]
-        );
-    }
-
-    #[test]
-    fn merge_usage_cache_uses_new_data_when_old_also_stale() {
-        let old: UsageCache = HashMap::from([(
-            "main".to_string(),
-            ProfileUsageCache {
-                usage_lines: vec![UsageLine::Text("5-hour  40.0% used".to_string())],
-                plan_type: Some("pro".to_string()),
-                is_stale: true,
-            },
-        )]);
-        let new: UsageCache = HashMap::from([(
-            "main".to_string(),
-            ProfileUsageCache {
-                usage_lines: vec![UsageLine::Text("Rate limited".to_string())],
-                plan_type: None,
-                is_stale: true,
-            },
-        )]);
-
-        let merged = merge_usage_cache(new, Some(&old));
-        let entry = &merged["main"];
-        assert!(entry.is_stale);
-        // Both old and new are stale, so new data is used (no fallback to stale old)
-        assert_eq!(
-            entry.usage_lines,
-            vec![UsageLine::Text("Rate limited".to_string())]
-        );
-    }
-
-    #[test]
-    fn merge_usage_cache_preserves_codex_data_on_error() {
-        let old: UsageCache = HashMap::from([(
-            "default".to_string(),
-            ProfileUsageCache {
-                usage_lines: vec![
-                    UsageLine::Text("5-hour  50.0% left".to_string()),
-                    UsageLine::Text("Weekly  70.0% left".to_string()),
-                ],
-                plan_type: None,
-                is_stale: false,
-            },
-        )]);
-        let new: UsageCache = HashMap::from([(
-            "default".to_string(),
-            ProfileUsageCache {
-                usage_lines: vec![UsageLine::Text("Error: connection refused".to_string())],
-                plan_type: None,
-                is_stale: true,
-            },
-        )]);
-
-        let merged = merge_usage_cache(new, Some(&old));
-        let entry = &merged["default"];
-        assert!(entry.is_stale);
-        // Old valid data is preserved instead of being replaced with error
-        assert_eq!(
-            entry.usage_lines,
-            vec![
-                UsageLine::Text("5-hour  50.0% left".to_string()),
-                UsageLine::Text("Weekly  70.0% left".to_string()),
-            ]
-        );
-    }
-
     #[test]
     fn build_dashboard_lines_shows_stale_indicator() {
         let tool_profiles = vec![(
@@ -1993,6 +1903,7 @@ mod tests {
                 usage_lines: vec![UsageLine::Text("5-hour  40.0% used".to_string())],
                 plan_type: Some("pro".to_string()),
                 is_stale: true,
+                rate_limited_until: None,
             },
         );
         let mut usage_caches = HashMap::new();
@@ -2027,6 +1938,7 @@ mod tests {
                 usage_lines: vec![UsageLine::Text("5-hour  40.0% used".to_string())],
                 plan_type: Some("pro".to_string()),
                 is_stale: false,
+                rate_limited_until: None,
             },
         );
         let mut usage_caches = HashMap::new();
@@ -2046,89 +1958,132 @@ mod tests {
         assert!(!lines.iter().any(|l| l.contains("(stale)")));
     }
 
-    // --- format_retry_after tests ---
+    // --- status_message rendering test ---
 
     #[test]
-    fn format_retry_after_minutes_and_seconds() {
-        let result = format_retry_after(Duration::from_secs(125));
-        assert_eq!(result, "Rate limited (resets in 2m 5s)");
-    }
+    fn build_dashboard_lines_shows_profile_meta_summary() {
+        let tool_profiles = vec![(Tool::Claude, vec!["work".to_string()], None)];
+        let selectable_items = build_selectable_items(&tool_profiles);
+        let profile_metas = HashMap::from([(
+            (Tool::Claude, "work".to_string()),
+            ProfileMeta {
+                email: Some("me@corp.example".to_string()),
+                note: Some("old seat".to_string()),
+                ..Default::default()
+            },
+        )]);
 
-    #[test]
-    fn format_retry_after_seconds_only() {
-        let result = format_retry_after(Duration::from_secs(30));
-        assert_eq!(result, "Rate limited (resets in 30s)");
-    }
+        let lines = DashboardView {
+            tool_profiles: &tool_profiles,
+            home_dirs: &HashMap::new(),
+            profile_metas: &profile_metas,
+            burn: &BurnTracker::default(),
+            usage_caches: &HashMap::new(),
+            pending_tools: &HashSet::new(),
+            selectable_items: &selectable_items,
+            selected: 0,
+            mode: &DashboardMode::Normal,
+            spinner_frame: 0,
+            status_message: None,
+            display_preference: DisplayPreference::Default,
+            updated_at: Local::now(),
+            auto_refresh: AutoRefresh::Off,
+        }
+        .build_lines();
 
-    #[test]
-    fn format_retry_after_zero() {
-        let result = format_retry_after(Duration::from_secs(0));
-        assert_eq!(result, "Rate limited");
+        assert!(
+            lines
+                .iter()
+                .any(|l| l.contains("work") && l.contains("me@corp.example · old seat"))
+        );
     }
 
     #[test]
-    fn format_retry_after_boundary_60s() {
-        let result = format_retry_after(Duration::from_secs(60));
-        assert_eq!(result, "Rate limited (resets in 1m 0s)");
-    }
-
-    // --- codex_usage_result tests ---
+    fn build_dashboard_lines_shows_burn_forecast_next_to_window() {
+        let tool_profiles = vec![(Tool::Claude, vec!["work".to_string()], None)];
+        let selectable_items = build_selectable_items(&tool_profiles);
+        let resets_at = Utc::now() + TimeDelta::hours(4);
+        let usage_caches = HashMap::from([(
+            Tool::Claude,
+            UsageCache::from([(
+                "work".to_string(),
+                make_entry(
+                    vec![UsageLine::Data {
+                        label: "5-hour".to_string(),
+                        percent: 30.0,
+                        resets_at: Some(resets_at),
+                    }],
+                    None,
+                ),
+            )]),
+        )]);
+        let mut burn = BurnTracker::default();
+        for (minutes_ago, percent) in [(60, 10.0), (0, 30.0)] {
+            burn.add(&history::Sample {
+                tool: "claude".to_string(),
+                profile: "work".to_string(),
+                window: "5-hour".to_string(),
+                percent,
+                resets_at: Some(resets_at),
+                fetched_at: Utc::now() - TimeDelta::minutes(minutes_ago),
+            });
+        }
 
-    #[test]
-    fn codex_usage_result_with_both_windows() {
-        let limits = RateLimits {
-            primary: Some(RateWindow {
-                used_percent: 50.0,
-                resets_at: Some(1700000000),
-            }),
-            secondary: Some(RateWindow {
-                used_percent: 30.0,
-                resets_at: Some(1700100000),
-            }),
-        };
-        let (lines, is_stale) = codex_usage_result(Ok(Some(limits)));
-        assert_eq!(lines.len(), 2);
-        assert!(matches!(&lines[0], UsageLine::Data { label, .. } if label == "5-hour"));
-        assert!(matches!(&lines[1], UsageLine::Data { label, .. } if label == "Weekly"));
-        assert!(!is_stale);
-    }
+        let lines = DashboardView {
+            tool_profiles: &tool_profiles,
+            home_dirs: &HashMap::new(),
+            profile_metas: &HashMap::new(),
+            burn: &burn,
+            usage_caches: &usage_caches,
+            pending_tools: &HashSet::new(),
+            selectable_items: &selectable_items,
+            selected: 0,
+            mode: &DashboardMode::Normal,
+            spinner_frame: 0,
+            status_message: None,
+            display_preference: DisplayPreference::Default,
+            updated_at: Local::now(),
+            auto_refresh: AutoRefresh::Off,
+        }
+        .build_lines();
 
-    #[test]
-    fn codex_usage_result_empty_windows_returns_no_data() {
-        let limits = RateLimits {
-            primary: None,
-            secondary: None,
-        };
-        let (lines, is_stale) = codex_usage_result(Ok(Some(limits)));
-        assert_eq!(
-            lines,
-            vec![UsageLine::Text("No usage data available".to_string())]
+        assert!(
+            lines
+                .iter()
+                .any(|l| l.contains("5-hour") && l.contains("exhausts at"))
         );
-        assert!(!is_stale);
     }
 
     #[test]
-    fn codex_usage_result_none_returns_no_data() {
-        let (lines, is_stale) = codex_usage_result(Ok(None));
-        assert_eq!(
-            lines,
-            vec![UsageLine::Text("No usage data available".to_string())]
-        );
-        assert!(!is_stale);
-    }
+    fn build_dashboard_lines_shows_tool_home_dir() {
+        let tool_profiles = vec![(Tool::Codex, vec![], None)];
+        let home_dirs = HashMap::from([(Tool::Codex, "/work/.codex".to_string())]);
+
+        let lines = DashboardView {
+            tool_profiles: &tool_profiles,
+            home_dirs: &home_dirs,
+            profile_metas: &HashMap::new(),
+            burn: &BurnTracker::default(),
+            usage_caches: &HashMap::new(),
+            pending_tools: &HashSet::new(),
+            selectable_items: &[],
+            selected: 0,
+            mode: &DashboardMode::Normal,
+            spinner_frame: 0,
+            status_message: None,
+            display_preference: DisplayPreference::Default,
+            updated_at: Local::now(),
+            auto_refresh: AutoRefresh::Off,
+        }
+        .build_lines();
 
-    #[test]
-    fn codex_usage_result_error_is_stale() {
-        let (lines, is_stale) = codex_usage_result(Err(anyhow::anyhow!("connection failed")));
-        assert_eq!(lines.len(), 1);
         assert!(
-            matches!(&lines[0], UsageLine::Text(t) if t.starts_with("Error: ") && t.contains("connection failed"))
+            lines
+                .iter()
+                .any(|l| l.starts_with("Codex CLI") && l.contains("/work/.codex"))
         );
-        assert!(is_stale);
     }
 
-    // --- status_message rendering test ---
-
     #[test]
     fn build_dashboard_lines_shows_status_message_in_red_before_footer() {
         let tool_profiles = vec![(
@@ -2140,6 +2095,9 @@ mod tests {
 
         let view = DashboardView {
             tool_profiles: &tool_profiles,
+            home_dirs: &HashMap::new(),
+            profile_metas: &HashMap::new(),
+            burn: &BurnTracker::default(),
             usage_caches: &HashMap::new(),
             pending_tools: &HashSet::new(),
             selectable_items: &selectable_items,
@@ -2148,6 +2106,8 @@ mod tests {
             spinner_frame: 0,
             status_message: Some("Failed to delete profile: not found"),
             display_preference: DisplayPreference::Default,
+            updated_at: Local::now(),
+            auto_refresh: AutoRefresh::Off,
         };
         let lines = view.build_lines();
 
@@ -2201,7 +2161,7 @@ mod tests {
                     .expect("status_message should be set on failure");
                 assert!(msg.starts_with("Failed to delete profile: "));
             }
-            DashboardAction::RefreshAfterDelete => {
+            DashboardAction::RefreshAfterDelete(..) => {
                 // If the delete somehow succeeded (unlikely in test), that's fine too
             }
             _ => panic!("expected Render or RefreshAfterDelete, got other action"),
@@ -1,10 +1,13 @@
+use std::path::Path;
+
 use chrono::{DateTime, Local, Utc};
 use serde::{Deserialize, Serialize};
 
 const BAR_WIDTH: usize = 20;
 const RESET: &str = "\x1b[0m";
 
-fn danger_color(used_percent: f64) -> &'static str {
+/// ANSI colour for a used percentage: green, then yellow above 50%, red above 80%.
+pub fn danger_color(used_percent: f64) -> &'static str {
     if used_percent > 80.0 {
         "\x1b[31m" // red
     } else if used_percent > 50.0 {
@@ -89,6 +92,47 @@ pub fn format_usage_line(
     )
 }
 
+pub fn capitalize_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        None => String::new(),
+        Some(c) => c.to_uppercase().to_string() + chars.as_str(),
+    }
+}
+
+/// Shortens `path` to start with `~` when it is inside `home`.
+pub fn abbreviate_home(path: &Path, home: Option<&Path>) -> String {
+    match home.and_then(|h| path.strip_prefix(h).ok()) {
+        Some(rest) if rest.as_os_str().is_empty() => "~".to_string(),
+        Some(rest) => format!("~/{}", rest.display()),
+        None => path.display().to_string(),
+    }
+}
+
+/// Formats rows as left-aligned columns separated by two spaces.
+pub fn format_table(header: &[&str], rows: &[Vec<String>]) -> Vec<String> {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    std::iter::once(&header)
+        .chain(rows)
+        .map(|row| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, &width)| format!("{:<width$}", cell))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+}
+
 pub fn format_reset_time(reset_utc: DateTime<Utc>) -> String {
     let local: DateTime<Local> = reset_utc.into();
     let now = Local::now();
@@ -157,6 +201,33 @@ mod tests {
         assert_eq!(danger_color(100.0), "\x1b[31m");
     }
 
+    #[test]
+    fn abbreviate_home_replaces_home_prefix() {
+        let home = Path::new("/home/me");
+
+        assert_eq!(
+            abbreviate_home(Path::new("/home/me/.claude"), Some(home)),
+            "~/.claude"
+        );
+        assert_eq!(abbreviate_home(home, Some(home)), "~");
+        assert_eq!(
+            abbreviate_home(Path::new("/work/.codex"), Some(home)),
+            "/work/.codex"
+        );
+        assert_eq!(
+            abbreviate_home(Path::new("/home/me/.codex"), None),
+            "/home/me/.codex"
+        );
+    }
+
+    #[test]
+    fn capitalize_first_capitalizes_first_char() {
+        assert_eq!(capitalize_first("pro"), "Pro");
+        assert_eq!(capitalize_first(""), "");
+        assert_eq!(capitalize_first("Pro"), "Pro");
+        assert_eq!(capitalize_first("日本語"), "日本語");
+    }
+
     #[test]
     fn format_reset_time_different_day() {
         use chrono::TimeZone;
@@ -0,0 +1,155 @@
+use std::fs;
+use std::path::Path;
+use std::process::ExitCode;
+
+use anyhow::{Context, Result, bail};
+use tempfile::TempDir;
+
+use crate::profile::ProfileManager;
+use crate::tool::Tool;
+use crate::{claude, codex};
+
+/// Prefix of the temporary config home created for each `aip exec` run. The
+/// leading dot keeps it out of the profile list.
+const ISOLATED_HOME_PREFIX: &str = ".exec-";
+
+fn isolated_manager(tool: Tool, home: &Path) -> Result<ProfileManager> {
+    match tool {
+        Tool::Claude => claude::profile::isolated_manager(home),
+        Tool::Codex => codex::profile::isolated_manager(home),
+    }
+}
+
+/// Settings that are copied from the global config home so the child behaves
+/// the same as a normal session.
+fn shared_config_files(tool: Tool) -> &'static [&'static str] {
+    match tool {
+        Tool::Claude => &["settings.json"],
+        Tool::Codex => &["config.toml"],
+    }
+}
+
+/// Creates an owner-only config home for one run, removed when dropped, with
+/// fresh copies of the shared settings.
+fn prepare_isolated_home(tool: Tool) -> Result<TempDir> {
+    let home = tempfile::Builder::new()
+        .prefix(ISOLATED_HOME_PREFIX)
+        .tempdir_in(tool.profiles_dir()?)
+        .context("failed to create isolated home")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(home.path(), fs::Permissions::from_mode(0o700))?;
+    }
+
+    let global_home = tool.home_dir()?;
+    for name in shared_config_files(tool) {
+        let src = global_home.join(name);
+        if src.exists() {
+            fs::copy(&src, home.path().join(name))
+                .with_context(|| format!("failed to copy {} into isolated home", name))?;
+        }
+    }
+    Ok(home)
+}
+
+fn exit_code_from(status: std::process::ExitStatus) -> ExitCode {
+    if let Some(code) = status.code() {
+        return ExitCode::from(u8::try_from(code).unwrap_or(1));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return ExitCode::from(u8::try_from(128 + signal).unwrap_or(1));
+        }
+    }
+    ExitCode::FAILURE
+}
+
+async fn run(program: &str, args: &[String], env: Option<(&str, &Path)>) -> Result<ExitCode> {
+    let mut command = tokio::process::Command::new(program);
+    command.args(args);
+    if let Some((key, value)) = env {
+        command.env(key, value);
+    }
+    let mut child = command
+        .spawn()
+        .with_context(|| format!("failed to run '{}'", program))?;
+
+    // Ctrl+C reaches the child through the terminal; keep aip alive so it
+    // can sync credentials back once the child exits.
+    let status = loop {
+        tokio::select! {
+            status = child.wait() => break status?,
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    };
+    Ok(exit_code_from(status))
+}
+
+pub async fn cmd_exec(tool_arg: &str, profile: &str, command: &[String]) -> Result<ExitCode> {
+    let tool: Tool = tool_arg.parse()?;
+    let Some((program, args)) = command.split_first() else {
+        bail!("no command given");
+    };
+
+    let profile_dir = tool.profile_dir(profile)?;
+    if !profile_dir.exists() {
+        bail!("profile '{}' does not exist for {}", profile, tool);
+    }
+
+    // The global credentials already belong to the current profile, and a
+    // second copy would race with them on token refresh.
+    if tool.current_profile()?.as_deref() == Some(profile) {
+        return run(program, args, None).await;
+    }
+
+    let home = prepare_isolated_home(tool)?;
+    let activated = isolated_manager(tool, home.path())?.activate(profile)?;
+
+    let result = run(program, args, Some((tool.config_dir_env(), home.path()))).await;
+
+    match tool {
+        Tool::Claude => {
+            claude::profile::sync_isolated_to_profile(home.path(), profile, activated).await
+        }
+        Tool::Codex => codex::profile::sync_isolated_to_profile(home.path(), profile, &activated),
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_code_from_passes_through_child_code() {
+        let status = std::process::Command::new("sh")
+            .args(["-c", "exit 7"])
+            .status()
+            .unwrap();
+
+        assert_eq!(exit_code_from(status), ExitCode::from(7));
+    }
+
+    #[test]
+    fn run_points_child_at_isolated_home() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let args = [
+            "-c".to_string(),
+            "test \"$CODEX_HOME\" = /tmp/isolated".to_string(),
+        ];
+
+        let code = rt
+            .block_on(run(
+                "sh",
+                &args,
+                Some(("CODEX_HOME", Path::new("/tmp/isolated"))),
+            ))
+            .unwrap();
+
+        assert_eq!(code, ExitCode::SUCCESS);
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::display::format_reset_time;
+use crate::history::Sample;
+use crate::notify::did_reset;
+use crate::tool::Tool;
+
+/// Projected outcome of a usage window at its current burn rate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Forecast {
+    ExhaustsAt(DateTime<Utc>),
+    SafeUntilReset,
+}
+
+impl Forecast {
+    pub fn label(&self) -> String {
+        match self {
+            Forecast::ExhaustsAt(at) => {
+                format!("\x1b[31mexhausts at {}\x1b[0m", format_reset_time(*at))
+            }
+            Forecast::SafeUntilReset => "\x1b[2msafe until reset\x1b[0m".to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Point {
+    at: DateTime<Utc>,
+    percent: f64,
+    resets_at: Option<DateTime<Utc>>,
+}
+
+/// Usage samples per window since its last reset, used to estimate burn rates.
+#[derive(Default)]
+pub struct BurnTracker {
+    points: HashMap<(Tool, String, String), Vec<Point>>,
+}
+
+impl BurnTracker {
+    pub fn add(&mut self, sample: &Sample) {
+        let Ok(tool) = sample.tool.parse::<Tool>() else {
+            return;
+        };
+        let points = self
+            .points
+            .entry((tool, sample.profile.clone(), sample.window.clone()))
+            .or_default();
+        if let Some(last) = points.last() {
+            if sample.fetched_at <= last.at {
+                return;
+            }
+            if did_reset(
+                last.percent,
+                last.resets_at,
+                sample.percent,
+                sample.resets_at,
+            ) {
+                points.clear();
+            }
+        }
+        points.push(Point {
+            at: sample.fetched_at,
+            percent: sample.percent,
+            resets_at: sample.resets_at,
+        });
+    }
+
+    pub fn forecast(&self, tool: Tool, profile: &str, window: &str) -> Option<Forecast> {
+        let points = self
+            .points
+            .get(&(tool, profile.to_string(), window.to_string()))?;
+        project(points)
+    }
+}
+
+/// Extrapolates the burn rate between the first and last point linearly.
+/// Projections too far out to represent count as no forecast.
+fn project(points: &[Point]) -> Option<Forecast> {
+    let (first, last) = (points.first()?, points.last()?);
+    let resets_at = last.resets_at?;
+    let hours = (last.at - first.at).num_seconds() as f64 / 3600.0;
+    if hours <= 0.0 || last.percent >= 100.0 {
+        return None;
+    }
+
+    let rate = (last.percent - first.percent) / hours;
+    if rate <= 0.0 {
+        return Some(Forecast::SafeUntilReset);
+    }
+    let remaining_secs = (100.0 - last.percent) / rate * 3600.0;
+    let exhausts_at = last
+        .at
+        .checked_add_signed(TimeDelta::try_seconds(remaining_secs as i64)?)?;
+    if exhausts_at >= resets_at {
+        Some(Forecast::SafeUntilReset)
+    } else {
+        Some(Forecast::ExhaustsAt(exhausts_at))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn sample(fetched_at: &str, percent: f64, resets_at: &str) -> Sample {
+        Sample {
+            tool: "claude".to_string(),
+            profile: "work".to_string(),
+            window: "5-hour".to_string(),
+            percent,
+            resets_at: Some(at(resets_at)),
+            fetched_at: at(fetched_at),
+        }
+    }
+
+    fn forecast(samples: &[Sample]) -> Option<Forecast> {
+        let mut tracker = BurnTracker::default();
+        for s in samples {
+            tracker.add(s);
+        }
+        tracker.forecast(Tool::Claude, "work", "5-hour")
+    }
+
+    #[test]
+    fn single_sample_has_no_forecast() {
+        assert_eq!(
+            forecast(&[sample("2026-01-01T10:00:00Z", 10.0, "2026-01-01T15:00:00Z")]),
+            None
+        );
+    }
+
+    #[test]
+    fn projects_exhaustion_before_reset() {
+        // 20% per hour from 40% reaches 100% three hours later.
+        let result = forecast(&[
+            sample("2026-01-01T10:00:00Z", 20.0, "2026-01-01T15:00:00Z"),
+            sample("2026-01-01T11:00:00Z", 40.0, "2026-01-01T15:00:00Z"),
+        ]);
+
+        assert_eq!(
+            result,
+            Some(Forecast::ExhaustsAt(at("2026-01-01T14:00:00Z")))
+        );
+    }
+
+    #[test]
+    fn slow_burn_is_safe_until_reset() {
+        let result = forecast(&[
+            sample("2026-01-01T10:00:00Z", 20.0, "2026-01-01T12:00:00Z"),
+            sample("2026-01-01T11:00:00Z", 30.0, "2026-01-01T12:00:00Z"),
+        ]);
+
+        assert_eq!(result, Some(Forecast::SafeUntilReset));
+    }
+
+    #[test]
+    fn flat_usage_is_safe_until_reset() {
+        let result = forecast(&[
+            sample("2026-01-01T10:00:00Z", 50.0, "2026-01-01T15:00:00Z"),
+            sample("2026-01-01T10:01:00Z", 50.0, "2026-01-01T15:00:00Z"),
+        ]);
+
+        assert_eq!(result, Some(Forecast::SafeUntilReset));
+    }
+
+    #[test]
+    fn reset_discards_earlier_samples() {
+        let result = forecast(&[
+            sample("2026-01-01T09:00:00Z", 0.0, "2026-01-01T10:00:00Z"),
+            sample("2026-01-01T09:30:00Z", 90.0, "2026-01-01T10:00:00Z"),
+            sample("2026-01-01T10:30:00Z", 5.0, "2026-01-01T15:30:00Z"),
+        ]);
+
+        assert_eq!(result, None);
+    }
+
+    #[test]
+    fn small_reset_drift_keeps_the_window() {
+        let result = forecast(&[
+            sample("2026-01-01T10:00:00Z", 20.0, "2026-01-01T15:00:00Z"),
+            sample("2026-01-01T11:00:00Z", 40.0, "2026-01-01T15:00:05Z"),
+        ]);
+
+        assert!(matches!(result, Some(Forecast::ExhaustsAt(_))));
+    }
+
+    #[test]
+    fn small_drop_keeps_the_burn_rate() {
+        let result = forecast(&[
+            sample("2026-01-01T10:00:00Z", 20.0, "2026-01-01T15:00:00Z"),
+            sample("2026-01-01T10:30:00Z", 42.0, "2026-01-01T15:00:00Z"),
+            sample("2026-01-01T11:00:00Z", 41.0, "2026-01-01T15:00:00Z"),
+        ]);
+
+        assert!(matches!(result, Some(Forecast::ExhaustsAt(_))));
+    }
+
+    #[test]
+    fn out_of_range_projection_has_no_forecast() {
+        let points = [
+            Point {
+                at: DateTime::<Utc>::MIN_UTC,
+                percent: 0.0,
+                resets_at: Some(DateTime::<Utc>::MAX_UTC),
+            },
+            Point {
+                at: DateTime::<Utc>::MIN_UTC + TimeDelta::days(1),
+                percent: 1e-9,
+                resets_at: Some(DateTime::<Utc>::MAX_UTC),
+            },
+        ];
+
+        assert_eq!(project(&points), None);
+    }
+
+    #[test]
+    fn exhausted_window_has_no_forecast() {
+        let result = forecast(&[
+            sample("2026-01-01T10:00:00Z", 80.0, "2026-01-01T15:00:00Z"),
+            sample("2026-01-01T11:00:00Z", 100.0, "2026-01-01T15:00:00Z"),
+        ]);
+
+        assert_eq!(result, None);
+    }
+}
@@ -1,35 +1,161 @@
-use std::fs;
-use std::path::Path;
+use std::fs::{self, File, TryLockError};
+use std::io::{self, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 
+const LOCK_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Mode for files anyone may read, as `fs::write` would create them.
+const DEFAULT_MODE: u32 = 0o666;
+/// Mode for credentials and their backups.
+const PRIVATE_MODE: u32 = 0o600;
+
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Another process held a lock for longer than we were willing to wait.
+#[derive(Debug)]
+pub struct LockBusy {
+    pub path: PathBuf,
+}
+
+impl LockBusy {
+    pub fn exit_code(&self) -> u8 {
+        7
+    }
+}
+
+impl std::fmt::Display for LockBusy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "another aip is busy (waiting for {} timed out); try again",
+            self.path.display()
+        )
+    }
+}
+
+impl std::error::Error for LockBusy {}
+
+/// An exclusive advisory lock on a file, released when dropped.
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// Locks `path`, creating it if needed, and retries until `timeout` elapses.
+    pub fn acquire(path: &Path, timeout: Duration) -> Result<Self> {
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path)?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            match file.try_lock() {
+                Ok(()) => return Ok(Self { _file: file }),
+                Err(TryLockError::WouldBlock) if Instant::now() < deadline => {
+                    std::thread::sleep(LOCK_RETRY_DELAY);
+                }
+                Err(TryLockError::WouldBlock) => {
+                    return Err(LockBusy {
+                        path: path.to_path_buf(),
+                    }
+                    .into());
+                }
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+/// A hidden temporary path next to `path`, unique per process and call.
+fn tmp_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let seq = TMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(".{}.{}.{}.tmp", name, std::process::id(), seq))
+}
+
 /// Atomically writes to `path` by first preparing a temporary file, then renaming it.
 ///
-/// # Single-writer assumption
+/// Every call uses its own temporary file, so concurrent writers never clobber each
+/// other's half-written data; the last rename wins. Read-modify-write sequences still
+/// need a [`FileLock`].
 ///
-/// The temporary file name is derived deterministically from `path` (same path with a `.tmp`
-/// extension). Concurrent callers targeting the same `path` will race on the same temporary
-/// file, causing data corruption or spurious errors. Callers must ensure that at most one
-/// writer targets a given `path` at any time.
-fn with_tmp_rename<F>(path: &Path, prepare: F) -> Result<()>
+/// The temporary file is created with `mode` (before the umask) and fsynced before the
+/// rename, and the directory is fsynced after it, so a crash leaves either the old or
+/// the new content in place.
+fn with_tmp_rename<F>(path: &Path, mode: u32, prepare: F) -> Result<()>
 where
-    F: FnOnce(&Path) -> std::io::Result<()>,
+    F: FnOnce(&mut File) -> io::Result<()>,
 {
-    let tmp = path.with_extension("tmp");
-    prepare(&tmp)?;
-    if let Err(e) = fs::rename(&tmp, path) {
+    let tmp = tmp_path(path);
+    let result = create_tmp(&tmp, mode)
+        .and_then(|mut file| {
+            prepare(&mut file)?;
+            file.sync_all()
+        })
+        .and_then(|()| fs::rename(&tmp, path));
+    if let Err(e) = result {
         let _ = fs::remove_file(&tmp);
         return Err(e.into());
     }
+    sync_parent(path)?;
     Ok(())
 }
 
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn create_tmp(path: &Path, mode: u32) -> io::Result<File> {
+    let mut options = File::options();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    options.mode(mode);
+    options.open(path)
+}
+
+/// Persists a rename by fsyncing the directory that holds `path`.
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    let parent = match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => Path::new("."),
+    };
+    File::open(parent)?.sync_all()
+}
+
+#[cfg(not(unix))]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Writes a non-secret file with the usual permissions (0666 minus the umask).
 pub fn atomic_write(path: &Path, content: &str) -> Result<()> {
-    with_tmp_rename(path, |tmp| fs::write(tmp, content))
+    with_tmp_rename(path, DEFAULT_MODE, |file| {
+        file.write_all(content.as_bytes())
+    })
+}
+
+/// Writes credentials or their backups owner-only (0600).
+pub fn atomic_write_private(path: &Path, content: &str) -> Result<()> {
+    with_tmp_rename(path, PRIVATE_MODE, |file| {
+        file.write_all(content.as_bytes())
+    })
 }
 
+/// Copies a credentials file owner-only (0600).
 pub fn atomic_copy(src: &Path, dst: &Path) -> Result<()> {
-    with_tmp_rename(dst, |tmp| fs::copy(src, tmp).map(|_| ()))
+    let mut src = File::open(src)?;
+    with_tmp_rename(dst, PRIVATE_MODE, |file| {
+        io::copy(&mut src, file).map(|_| ())
+    })
 }
 
 #[cfg(test)]
@@ -64,8 +190,43 @@ mod tests {
 
         atomic_write(&path, "content").unwrap();
 
-        let tmp_path = path.with_extension("tmp");
-        assert!(!tmp_path.exists());
+        let names: Vec<_> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, ["test.txt"]);
+    }
+
+    #[test]
+    fn tmp_paths_are_unique_and_hidden() {
+        let path = Path::new("/profiles/_current");
+
+        let first = tmp_path(path);
+        let second = tmp_path(path);
+
+        assert_ne!(first, second);
+        assert_eq!(first.parent(), path.parent());
+        assert!(
+            first
+                .file_name()
+                .unwrap()
+                .to_string_lossy()
+                .starts_with("._current.")
+        );
+    }
+
+    #[test]
+    fn file_lock_times_out_while_held() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".lock");
+
+        let held = FileLock::acquire(&path, Duration::ZERO).unwrap();
+        let err = FileLock::acquire(&path, Duration::from_millis(100)).unwrap_err();
+        assert!(err.downcast_ref::<LockBusy>().is_some());
+        assert!(err.to_string().contains("another aip is busy"));
+
+        drop(held);
+        assert!(FileLock::acquire(&path, Duration::ZERO).is_ok());
     }
 
     #[test]
@@ -80,6 +241,45 @@ mod tests {
         assert_eq!(fs::read_to_string(&dst).unwrap(), "copy me");
     }
 
+    #[cfg(unix)]
+    #[test]
+    fn private_write_and_copy_create_owner_only_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src.json");
+        let dst = dir.path().join("dst.json");
+        fs::write(&src, "{}").unwrap();
+        fs::set_permissions(&src, fs::Permissions::from_mode(0o644)).unwrap();
+        fs::write(&dst, "old").unwrap();
+        fs::set_permissions(&dst, fs::Permissions::from_mode(0o644)).unwrap();
+
+        atomic_copy(&src, &dst).unwrap();
+        let written = dir.path().join("written.json");
+        atomic_write_private(&written, "{}").unwrap();
+
+        for path in [&dst, &written] {
+            let mode = fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode, 0o600, "{}", path.display());
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn atomic_write_uses_default_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let plain = dir.path().join("plain.prom");
+        fs::write(&plain, "").unwrap();
+        let written = dir.path().join("written.prom");
+
+        atomic_write(&written, "aip_usage_percent 1\n").unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&written), mode(&plain));
+    }
+
     #[test]
     fn atomic_copy_fails_for_missing_source() {
         let dir = tempfile::tempdir().unwrap();
@@ -0,0 +1,404 @@
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Local, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{self, Config};
+use crate::display::{self, format_reset_time};
+use crate::fs_util::{FileLock, atomic_write};
+use crate::tool::Tool;
+use crate::usage::{UsageCache, UsageLine};
+
+/// One usage window reading, as appended to `history.jsonl`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sample {
+    pub tool: String,
+    pub profile: String,
+    pub window: String,
+    pub percent: f64,
+    pub resets_at: Option<DateTime<Utc>>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// How long to wait for another process writing the history.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn history_path() -> Result<PathBuf> {
+    Ok(config::aip_dir()?.join("history.jsonl"))
+}
+
+/// Samples for every freshly fetched window in `cache`; stale entries are skipped.
+pub fn samples_from_cache(
+    tool: Tool,
+    cache: &UsageCache,
+    fetched_at: DateTime<Utc>,
+) -> Vec<Sample> {
+    let mut samples = Vec::new();
+    for (profile, entry) in cache {
+        if entry.is_stale {
+            continue;
+        }
+        for line in &entry.usage_lines {
+            if let UsageLine::Data {
+                label,
+                percent,
+                resets_at,
+            } = line
+            {
+                samples.push(Sample {
+                    tool: tool.id().to_string(),
+                    profile: profile.clone(),
+                    window: label.clone(),
+                    percent: *percent,
+                    resets_at: *resets_at,
+                    fetched_at,
+                });
+            }
+        }
+    }
+    samples.sort_by(|a, b| (&a.profile, &a.window).cmp(&(&b.profile, &b.window)));
+    samples
+}
+
+fn append_samples(path: &Path, samples: &[Sample]) -> Result<()> {
+    if samples.is_empty() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut buf = String::new();
+    for sample in samples {
+        buf.push_str(&serde_json::to_string(sample)?);
+        buf.push('\n');
+    }
+    // A single append keeps concurrent writers from interleaving lines.
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(buf.as_bytes())?;
+    Ok(())
+}
+
+/// Drops samples fetched before `cutoff`, along with lines that fail to parse.
+///
+/// The file is only rewritten once its oldest sample is a day past the
+/// cutoff, so most appends just read the first line.
+fn compact(path: &Path, cutoff: DateTime<Utc>) -> Result<()> {
+    let file = match fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut first = String::new();
+    BufReader::new(file).read_line(&mut first)?;
+    let oldest = serde_json::from_str::<Sample>(&first).map(|s| s.fetched_at);
+    if oldest.is_ok_and(|oldest| oldest >= cutoff - TimeDelta::days(1)) {
+        return Ok(());
+    }
+    let kept: String = fs::read_to_string(path)?
+        .lines()
+        .filter(|line| serde_json::from_str::<Sample>(line).is_ok_and(|s| s.fetched_at >= cutoff))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    atomic_write(path, &kept)
+}
+
+/// Appends `samples` under the history lock, then drops samples older than
+/// `retention`; `None` keeps them forever.
+fn record_to(
+    path: &Path,
+    samples: &[Sample],
+    retention: Option<Duration>,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    if samples.is_empty() {
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let _lock = FileLock::acquire(&path.with_extension("jsonl.lock"), LOCK_TIMEOUT)?;
+    append_samples(path, samples)?;
+    if let Some(retention) = retention {
+        compact(path, now - TimeDelta::from_std(retention)?)?;
+    }
+    Ok(())
+}
+
+/// Appends the fetched windows in `cache` to the history store.
+pub fn record(tool: Tool, cache: &UsageCache) -> Result<()> {
+    let now = Utc::now();
+    record_to(
+        &history_path()?,
+        &samples_from_cache(tool, cache, now),
+        Config::load().history_retention(),
+        now,
+    )
+}
+
+/// Samples fetched at or after `since`, skipping lines that fail to parse.
+fn read_samples(path: &Path, since: DateTime<Utc>) -> Result<Vec<Sample>> {
+    let file = match fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut samples = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let Ok(sample) = serde_json::from_str::<Sample>(&line?) else {
+            continue;
+        };
+        if sample.fetched_at >= since {
+            samples.push(sample);
+        }
+    }
+    Ok(samples)
+}
+
+/// Loads samples for `tool`/`profile` fetched at or after `since`.
+pub fn load(
+    tool: Option<Tool>,
+    profile: Option<&str>,
+    since: DateTime<Utc>,
+) -> Result<Vec<Sample>> {
+    let samples = read_samples(&history_path()?, since)?;
+    Ok(filter(samples, tool, profile))
+}
+
+fn filter(samples: Vec<Sample>, tool: Option<Tool>, profile: Option<&str>) -> Vec<Sample> {
+    samples
+        .into_iter()
+        .filter(|s| tool.is_none_or(|t| s.tool == t.id()))
+        .filter(|s| profile.is_none_or(|p| s.profile == p))
+        .collect()
+}
+
+/// Parses spans like `30m`, `12h`, `7d` or `2w`.
+pub fn parse_span(s: &str) -> Result<TimeDelta> {
+    let invalid = || anyhow!("invalid duration '{}': expected e.g. 30m, 12h, 7d or 2w", s);
+    let s = s.trim();
+    let unit_at = s.len().checked_sub(1).ok_or_else(invalid)?;
+    let (amount, unit) = s.split_at(unit_at);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    let span = match unit {
+        "m" => TimeDelta::try_minutes(amount),
+        "h" => TimeDelta::try_hours(amount),
+        "d" => TimeDelta::try_days(amount),
+        "w" => TimeDelta::try_weeks(amount),
+        _ => None,
+    };
+    match span {
+        Some(span) if amount >= 0 => Ok(span),
+        _ => Err(invalid()),
+    }
+}
+
+fn format_table(samples: &[Sample]) -> Vec<String> {
+    let rows: Vec<Vec<String>> = samples
+        .iter()
+        .map(|s| {
+            vec![
+                s.fetched_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+                s.tool.clone(),
+                s.profile.clone(),
+                s.window.clone(),
+                format!("{:.1}%", s.percent),
+                s.resets_at.map(format_reset_time).unwrap_or_default(),
+            ]
+        })
+        .collect();
+    display::format_table(
+        &["FETCHED", "TOOL", "PROFILE", "WINDOW", "USED", "RESETS"],
+        &rows,
+    )
+}
+
+pub fn cmd_history(
+    tool_arg: Option<String>,
+    profile_arg: Option<String>,
+    since: &str,
+    json: bool,
+) -> Result<()> {
+    let tool = tool_arg.map(|t| t.parse::<Tool>()).transpose()?;
+    if profile_arg.is_some() && tool.is_none() {
+        bail!("a tool is required when filtering by profile");
+    }
+    let since = Utc::now() - parse_span(since)?;
+    let samples = load(tool, profile_arg.as_deref(), since)?;
+
+    if json {
+        let output = serde_json::json!({ "samples": samples });
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else if samples.is_empty() {
+        println!("No usage history recorded in this period.");
+    } else {
+        for line in format_table(&samples) {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage::ProfileUsageCache;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn sample(tool: &str, profile: &str, fetched_at: &str) -> Sample {
+        Sample {
+            tool: tool.to_string(),
+            profile: profile.to_string(),
+            window: "5-hour".to_string(),
+            percent: 10.0,
+            resets_at: None,
+            fetched_at: at(fetched_at),
+        }
+    }
+
+    #[test]
+    fn samples_from_cache_keeps_fresh_data_lines_only() {
+        let fetched_at = at("2026-01-01T00:00:00Z");
+        let cache = UsageCache::from([
+            (
+                "work".to_string(),
+                ProfileUsageCache {
+                    usage_lines: vec![
+                        UsageLine::Data {
+                            label: "5-hour".to_string(),
+                            percent: 42.0,
+                            resets_at: Some(at("2026-01-01T03:00:00Z")),
+                        },
+                        UsageLine::Text("note".to_string()),
+                    ],
+                    plan_type: None,
+                    is_stale: false,
+                    rate_limited_until: None,
+                },
+            ),
+            (
+                "old".to_string(),
+                ProfileUsageCache {
+                    usage_lines: vec![UsageLine::Data {
+                        label: "5-hour".to_string(),
+                        percent: 1.0,
+                        resets_at: None,
+                    }],
+                    plan_type: None,
+                    is_stale: true,
+                    rate_limited_until: None,
+                },
+            ),
+        ]);
+
+        let samples = samples_from_cache(Tool::Claude, &cache, fetched_at);
+
+        assert_eq!(
+            samples,
+            vec![Sample {
+                tool: "claude".to_string(),
+                profile: "work".to_string(),
+                window: "5-hour".to_string(),
+                percent: 42.0,
+                resets_at: Some(at("2026-01-01T03:00:00Z")),
+                fetched_at,
+            }]
+        );
+    }
+
+    #[test]
+    fn append_and_read_round_trip_filtered_by_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("aip").join("history.jsonl");
+
+        append_samples(&path, &[sample("claude", "work", "2026-01-01T00:00:00Z")]).unwrap();
+        append_samples(&path, &[sample("codex", "dev", "2026-01-08T00:00:00Z")]).unwrap();
+        let mut content = fs::read_to_string(&path).unwrap();
+        content.push_str("not json\n");
+        fs::write(&path, content).unwrap();
+
+        let all = read_samples(&path, at("2025-01-01T00:00:00Z")).unwrap();
+        assert_eq!(all.len(), 2);
+
+        let recent = read_samples(&path, at("2026-01-05T00:00:00Z")).unwrap();
+        assert_eq!(recent, vec![sample("codex", "dev", "2026-01-08T00:00:00Z")]);
+    }
+
+    #[test]
+    fn record_drops_samples_past_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.jsonl");
+        let week = Some(Duration::from_secs(7 * 86400));
+        append_samples(
+            &path,
+            &[
+                sample("claude", "work", "2026-01-01T00:00:00Z"),
+                sample("claude", "work", "2026-01-09T00:00:00Z"),
+            ],
+        )
+        .unwrap();
+
+        // The oldest sample is within a day of the cutoff: no rewrite yet.
+        let first = sample("claude", "work", "2026-01-09T12:00:00Z");
+        record_to(&path, &[first], week, at("2026-01-08T12:00:00Z")).unwrap();
+        assert_eq!(
+            read_samples(&path, DateTime::<Utc>::MIN_UTC).unwrap().len(),
+            3
+        );
+
+        let second = sample("claude", "work", "2026-01-10T00:00:00Z");
+        record_to(&path, &[second], week, at("2026-01-10T00:00:00Z")).unwrap();
+        let kept = read_samples(&path, DateTime::<Utc>::MIN_UTC).unwrap();
+        assert_eq!(kept.len(), 3);
+        assert_eq!(kept[0].fetched_at, at("2026-01-09T00:00:00Z"));
+    }
+
+    #[test]
+    fn read_samples_returns_empty_when_missing() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let samples = read_samples(&dir.path().join("history.jsonl"), Utc::now()).unwrap();
+
+        assert!(samples.is_empty());
+    }
+
+    #[test]
+    fn filter_matches_tool_and_profile() {
+        let samples = vec![
+            sample("claude", "work", "2026-01-01T00:00:00Z"),
+            sample("claude", "home", "2026-01-01T00:00:00Z"),
+            sample("codex", "work", "2026-01-01T00:00:00Z"),
+        ];
+
+        assert_eq!(filter(samples.clone(), None, None).len(), 3);
+        assert_eq!(filter(samples.clone(), Some(Tool::Claude), None).len(), 2);
+        assert_eq!(
+            filter(samples, Some(Tool::Claude), Some("work")),
+            vec![sample("claude", "work", "2026-01-01T00:00:00Z")]
+        );
+    }
+
+    #[test]
+    fn parse_span_accepts_units() {
+        assert_eq!(parse_span("30m").unwrap(), TimeDelta::minutes(30));
+        assert_eq!(parse_span("12h").unwrap(), TimeDelta::hours(12));
+        assert_eq!(parse_span("7d").unwrap(), TimeDelta::days(7));
+        assert_eq!(parse_span("2w").unwrap(), TimeDelta::weeks(2));
+    }
+
+    #[test]
+    fn parse_span_rejects_invalid_input() {
+        for input in ["", "d", "7", "7y", "-1d", "1.5h"] {
+            assert!(parse_span(input).is_err(), "{input} should be rejected");
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+
+use crate::display::{self, capitalize_first};
+use crate::meta::ProfileMeta;
+use crate::tool::Tool;
+use crate::{claude, codex};
+
+#[derive(Debug, Serialize)]
+struct ListOutput {
+    tools: Vec<ToolListing>,
+}
+
+#[derive(Debug, Serialize)]
+struct ToolListing {
+    tool: &'static str,
+    current: Option<String>,
+    profiles: Vec<ProfileListing>,
+}
+
+#[derive(Debug, Serialize)]
+struct ProfileListing {
+    name: String,
+    current: bool,
+    plan_type: Option<String>,
+    account_id: Option<String>,
+    email: Option<String>,
+    organization: Option<String>,
+    created_at: Option<DateTime<Utc>>,
+    last_switched_at: Option<DateTime<Utc>>,
+    switch_reason: Option<String>,
+    note: Option<String>,
+}
+
+impl ProfileListing {
+    fn new(name: String, current: bool, plan_type: Option<String>, meta: ProfileMeta) -> Self {
+        Self {
+            name,
+            current,
+            plan_type,
+            account_id: meta.account_id,
+            email: meta.email,
+            organization: meta.organization,
+            created_at: meta.created_at,
+            last_switched_at: meta.last_switched_at,
+            switch_reason: meta.switch_reason,
+            note: meta.note,
+        }
+    }
+}
+
+fn plan_type(tool: Tool, profile: &str) -> Option<String> {
+    let dir = tool.profile_dir(profile).ok()?;
+    match tool {
+        Tool::Claude => {
+            claude::usage::read_profile_info(&dir.join("credentials.json"))
+                .ok()?
+                .plan_type
+        }
+        Tool::Codex => {
+            codex::usage::read_id_token_claims(&dir.join("auth.json"))
+                .ok()?
+                .plan_type
+        }
+    }
+}
+
+fn collect(tools: &[Tool]) -> Result<ListOutput> {
+    let mut listings = Vec::new();
+    for &tool in tools {
+        let current = tool.current_profile()?;
+        let profiles = tool
+            .list_profiles()?
+            .into_iter()
+            .map(|name| {
+                let meta = tool
+                    .profile_dir(&name)
+                    .map(|dir| ProfileMeta::load_for_display(tool, &dir))
+                    .unwrap_or_default();
+                ProfileListing::new(
+                    name.clone(),
+                    current.as_deref() == Some(name.as_str()),
+                    plan_type(tool, &name),
+                    meta,
+                )
+            })
+            .collect();
+        listings.push(ToolListing {
+            tool: tool.id(),
+            current,
+            profiles,
+        });
+    }
+    Ok(ListOutput { tools: listings })
+}
+
+fn format_table(output: &ListOutput) -> Vec<String> {
+    let rows: Vec<Vec<String>> = output
+        .tools
+        .iter()
+        .flat_map(|t| {
+            t.profiles.iter().map(|p| {
+                vec![
+                    t.tool.to_string(),
+                    p.name.clone(),
+                    if p.current { "*" } else { "" }.to_string(),
+                    p.plan_type
+                        .as_deref()
+                        .map(capitalize_first)
+                        .unwrap_or_default(),
+                    p.email.clone().unwrap_or_default(),
+                    p.last_switched_at
+                        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_default(),
+                    p.note.clone().unwrap_or_default(),
+                ]
+            })
+        })
+        .collect();
+
+    display::format_table(
+        &[
+            "TOOL",
+            "PROFILE",
+            "CURRENT",
+            "PLAN",
+            "EMAIL",
+            "LAST SWITCHED",
+            "NOTE",
+        ],
+        &rows,
+    )
+}
+
+pub fn cmd_list(tool_arg: Option<String>, json: bool) -> Result<()> {
+    let tools = match tool_arg {
+        Some(t) => vec![t.parse()?],
+        None => Tool::ALL.to_vec(),
+    };
+    let output = collect(&tools)?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        for line in format_table(&output) {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_output() -> ListOutput {
+        ListOutput {
+            tools: vec![
+                ToolListing {
+                    tool: "claude",
+                    current: Some("personal".to_string()),
+                    profiles: vec![
+                        ProfileListing::new(
+                            "personal".to_string(),
+                            true,
+                            Some("max".to_string()),
+                            ProfileMeta {
+                                email: Some("me@example.com".to_string()),
+                                note: Some("home".to_string()),
+                                ..Default::default()
+                            },
+                        ),
+                        ProfileListing::new(
+                            "work".to_string(),
+                            false,
+                            None,
+                            ProfileMeta::default(),
+                        ),
+                    ],
+                },
+                ToolListing {
+                    tool: "codex",
+                    current: None,
+                    profiles: vec![],
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn json_output_has_stable_shape() {
+        let value = serde_json::to_value(sample_output()).unwrap();
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "tools": [
+                    {
+                        "tool": "claude",
+                        "current": "personal",
+                        "profiles": [
+                            {
+                                "name": "personal",
+                                "current": true,
+                                "plan_type": "max",
+                                "account_id": null,
+                                "email": "me@example.com",
+                                "organization": null,
+                                "created_at": null,
+                                "last_switched_at": null,
+                                "switch_reason": null,
+                                "note": "home"
+                            },
+                            {
+                                "name": "work",
+                                "current": false,
+                                "plan_type": null,
+                                "account_id": null,
+                                "email": null,
+                                "organization": null,
+                                "created_at": null,
+                                "last_switched_at": null,
+                                "switch_reason": null,
+                                "note": null
+                            }
+                        ]
+                    },
+                    { "tool": "codex", "current": null, "profiles": [] }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn format_table_aligns_columns() {
+        let lines = format_table(&sample_output());
+
+        assert_eq!(
+            lines,
+            vec![
+                "TOOL    PROFILE   CURRENT  PLAN  EMAIL           LAST SWITCHED  NOTE",
+                "claude  personal  *        Max   me@example.com                 home",
+                "claude  work",
+            ]
+        );
+    }
+}
@@ -1,37 +1,132 @@
+mod auto_switch;
+mod backup;
 mod claude;
 mod cli;
 mod codex;
 mod config;
+mod credential_store;
 mod dashboard;
 mod display;
+mod exec;
+mod forecast;
 mod fs_util;
+mod history;
 mod http;
+mod list;
+mod meta;
+mod metrics;
+mod notify;
+mod profile;
+mod statusline;
 mod tool;
+mod trash;
+mod usage;
+mod usage_cache;
+mod watch;
+
+use std::process::ExitCode;
 
 use anyhow::Result;
 use clap::Parser;
 use dialoguer::{Confirm, Input, Select};
 
 use cli::{Cli, Command};
+use fs_util::LockBusy;
+use meta::ProfileMeta;
+use profile::SwitchError;
 use tool::Tool;
+use usage::{UsageAboveThreshold, UsageUnavailable};
 
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
     let rt = tokio::runtime::Runtime::new()?;
     let result = rt.block_on(async {
         let cli = Cli::parse_from(cli::normalize_short_flags(std::env::args_os()));
+        if !cli.command.as_ref().is_some_and(Command::is_read_only) {
+            profile::recover_interrupted_switches();
+        }
 
         match cli.command {
             None => dashboard::cmd_dashboard().await?,
-            Some(Command::Save { tool, profile }) => cmd_save(tool, profile)?,
+            Some(Command::Save { tool, profile }) => cmd_save(tool, profile).await?,
+            Some(Command::List { tool, json }) => list::cmd_list(tool, json)?,
+            Some(Command::Switch {
+                tool,
+                profile,
+                best,
+            }) => match profile {
+                Some(profile) if !best => cmd_switch(&tool, &profile).await?,
+                _ => auto_switch::cmd_switch_best(tool.parse()?).await?,
+            },
+            Some(Command::Note {
+                tool,
+                profile,
+                note,
+            }) => cmd_note(&tool, &profile, note)?,
+            Some(Command::Exec {
+                tool,
+                profile,
+                command,
+            }) => return exec::cmd_exec(&tool, &profile, &command).await,
+            Some(Command::History {
+                tool,
+                profile,
+                since,
+                json,
+            }) => history::cmd_history(tool, profile, &since, json)?,
+            Some(Command::ServeMetrics {
+                listen,
+                textfile,
+                interval,
+            }) => metrics::cmd_serve_metrics(listen, textfile, interval).await?,
+            Some(Command::Watch { interval, json }) => watch::cmd_watch(interval, json).await?,
+            Some(Command::Statusline { tool }) => statusline::cmd_statusline(tool)?,
+            Some(Command::Trash { action }) => trash::cmd_trash(action)?,
+            Some(Command::Restore {
+                tool,
+                profile,
+                version,
+                list,
+            }) => cmd_restore(&tool, &profile, version, list)?,
+            Some(Command::Usage {
+                tool,
+                profile,
+                format,
+                fail_above,
+                cached,
+            }) => usage::cmd_usage(tool, profile, format, fail_above, cached).await?,
         }
 
-        Ok(())
+        Ok::<ExitCode, anyhow::Error>(ExitCode::SUCCESS)
     });
 
     // Don't wait for the blocking key-reader thread to finish.
     rt.shutdown_background();
 
-    result
+    match result {
+        Ok(code) => Ok(code),
+        Err(e) => match exit_code_for(&e) {
+            Some(code) => {
+                eprintln!("Error: {:?}", e);
+                Ok(ExitCode::from(code))
+            }
+            None => Err(e),
+        },
+    }
+}
+
+/// Maps errors that scripts may want to tell apart to their exit codes.
+fn exit_code_for(e: &anyhow::Error) -> Option<u8> {
+    if let Some(switch_err) = e.downcast_ref::<SwitchError>() {
+        return Some(switch_err.exit_code());
+    }
+    if let Some(busy) = e.downcast_ref::<LockBusy>() {
+        return Some(busy.exit_code());
+    }
+    if let Some(above) = e.downcast_ref::<UsageAboveThreshold>() {
+        return Some(above.exit_code());
+    }
+    e.downcast_ref::<UsageUnavailable>()
+        .map(UsageUnavailable::exit_code)
 }
 
 fn select_tool() -> Result<Option<Tool>> {
@@ -45,7 +140,7 @@ fn select_tool() -> Result<Option<Tool>> {
     Ok(selection.map(|i| Tool::ALL[i]))
 }
 
-fn cmd_save(tool_arg: Option<String>, profile_arg: Option<String>) -> Result<()> {
+async fn cmd_save(tool_arg: Option<String>, profile_arg: Option<String>) -> Result<()> {
     let tool = match tool_arg {
         Some(t) => t.parse()?,
         None => {
@@ -75,10 +170,72 @@ fn cmd_save(tool_arg: Option<String>, profile_arg: Option<String>) -> Result<()>
     }
 
     match tool {
-        Tool::Claude => claude::profile::save(&name)?,
+        Tool::Claude => claude::profile::save(&name).await?,
         Tool::Codex => codex::profile::save(&name)?,
     }
 
     println!("Saved profile '{}' for {}", name, tool);
     Ok(())
 }
+
+async fn cmd_switch(tool_arg: &str, profile: &str) -> Result<()> {
+    let tool: Tool = tool_arg.parse()?;
+
+    if tool.current_profile()?.as_deref() == Some(profile) {
+        println!("Profile '{}' is already active for {}", profile, tool);
+        return Ok(());
+    }
+
+    profile::switch(tool, profile).await?;
+
+    println!("Switched to profile '{}' for {}", profile, tool);
+    Ok(())
+}
+
+fn cmd_note(tool_arg: &str, profile: &str, note: Option<String>) -> Result<()> {
+    let tool: Tool = tool_arg.parse()?;
+    let profile_dir = tool.profile_dir(profile)?;
+    if !profile_dir.exists() {
+        anyhow::bail!("profile '{}' does not exist for {}", profile, tool);
+    }
+
+    let note = note.filter(|n| !n.trim().is_empty());
+    let cleared = note.is_none();
+    let _lock = tool.lock_profiles()?;
+    ProfileMeta::update(&profile_dir, |m| m.note = note)?;
+
+    if cleared {
+        println!("Cleared note for profile '{}' ({})", profile, tool);
+    } else {
+        println!("Updated note for profile '{}' ({})", profile, tool);
+    }
+    Ok(())
+}
+
+fn cmd_restore(tool_arg: &str, profile: &str, version: Option<usize>, list: bool) -> Result<()> {
+    let tool: Tool = tool_arg.parse()?;
+    let profile_dir = tool.profile_dir(profile)?;
+    if !profile_dir.exists() {
+        anyhow::bail!("profile '{}' does not exist for {}", profile, tool);
+    }
+
+    if list {
+        let backups = backup::versions(&profile_dir)?;
+        if backups.is_empty() {
+            println!("No credential backups for profile '{}' ({})", profile, tool);
+        } else {
+            for line in backup::format_table(&backups) {
+                println!("{}", line);
+            }
+        }
+        return Ok(());
+    }
+
+    let version = version.unwrap_or(1);
+    profile::restore(tool, profile, version)?;
+    println!(
+        "Restored profile '{}' ({}) from backup {}",
+        profile, tool, version
+    );
+    Ok(())
+}
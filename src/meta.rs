@@ -0,0 +1,141 @@
+use std::path::Path;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::codex;
+use crate::fs_util;
+use crate::tool::Tool;
+
+const META_FILE: &str = "meta.json";
+
+/// Per-profile metadata stored next to the credentials in `meta.json`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProfileMeta {
+    /// Stable account identifier used to refuse syncing another account's
+    /// credentials into this profile.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<String>,
+    /// Hash of the last access token confirmed to belong to `account_id`, so
+    /// later syncs with the same token skip the account lookup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub organization: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_switched_at: Option<DateTime<Utc>>,
+    /// Why the auto-switch policy last moved to this profile; cleared by manual switches.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub switch_reason: Option<String>,
+    /// Free-text note set with `aip note`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl ProfileMeta {
+    /// Loads a profile's metadata, treating a missing or unreadable file as empty.
+    pub fn load(profile_dir: &Path) -> Self {
+        std::fs::read_to_string(profile_dir.join(META_FILE))
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    /// Loads metadata for display, filling in what can be read offline from
+    /// the stored credentials (the Codex `id_token` email).
+    pub fn load_for_display(tool: Tool, profile_dir: &Path) -> Self {
+        let mut meta = Self::load(profile_dir);
+        if tool == Tool::Codex && meta.email.is_none() {
+            meta.email = codex::usage::read_id_token_claims(&profile_dir.join("auth.json"))
+                .ok()
+                .and_then(|claims| claims.email);
+        }
+        meta
+    }
+
+    /// Loads, modifies and saves a profile's metadata.
+    pub fn update(profile_dir: &Path, f: impl FnOnce(&mut ProfileMeta)) -> Result<()> {
+        let mut meta = Self::load(profile_dir);
+        f(&mut meta);
+        meta.save(profile_dir)
+    }
+
+    /// Email, organization and note joined for a one-line description.
+    pub fn summary(&self) -> Option<String> {
+        let parts: Vec<&str> = [&self.email, &self.organization, &self.note]
+            .into_iter()
+            .filter_map(|p| p.as_deref())
+            .filter(|p| !p.is_empty())
+            .collect();
+        (!parts.is_empty()).then(|| parts.join(" · "))
+    }
+
+    pub fn save(&self, profile_dir: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs_util::atomic_write(&profile_dir.join(META_FILE), &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_returns_default_when_missing_or_invalid() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(ProfileMeta::load(dir.path()), ProfileMeta::default());
+
+        std::fs::write(dir.path().join(META_FILE), "not json").unwrap();
+        assert_eq!(ProfileMeta::load(dir.path()), ProfileMeta::default());
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let meta = ProfileMeta {
+            account_id: Some("acct-1".to_string()),
+            email: Some("a@example.com".to_string()),
+            created_at: DateTime::from_timestamp(1700000000, 0),
+            note: Some("team seat".to_string()),
+            ..Default::default()
+        };
+
+        meta.save(dir.path()).unwrap();
+
+        assert_eq!(ProfileMeta::load(dir.path()), meta);
+    }
+
+    #[test]
+    fn update_preserves_other_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        ProfileMeta {
+            email: Some("a@example.com".to_string()),
+            ..Default::default()
+        }
+        .save(dir.path())
+        .unwrap();
+
+        ProfileMeta::update(dir.path(), |m| m.note = Some("old seat".to_string())).unwrap();
+
+        let meta = ProfileMeta::load(dir.path());
+        assert_eq!(meta.email.as_deref(), Some("a@example.com"));
+        assert_eq!(meta.note.as_deref(), Some("old seat"));
+    }
+
+    #[test]
+    fn summary_joins_present_fields() {
+        let meta = ProfileMeta {
+            email: Some("a@example.com".to_string()),
+            note: Some("personal".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(meta.summary().as_deref(), Some("a@example.com · personal"));
+        assert_eq!(ProfileMeta::default().summary(), None);
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use chrono::Utc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::RwLock;
+
+use crate::config::Config;
+use crate::fs_util::atomic_write;
+use crate::tool::Tool;
+use crate::usage::{
+    UsageCache, UsageLine, merge_usage_cache, prefetch_claude_usage, prefetch_codex_usage,
+    rate_limited_until,
+};
+
+const DEFAULT_LISTEN: &str = "127.0.0.1:9464";
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+const MAX_REQUEST_BYTES: usize = 8192;
+/// How long a client may take to send its request headers.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Latest usage per tool plus error counters accumulated since startup.
+#[derive(Default)]
+struct MetricsState {
+    usage_caches: HashMap<Tool, UsageCache>,
+    fetch_errors: BTreeMap<(&'static str, String), u64>,
+}
+
+impl MetricsState {
+    fn update(&mut self, tool: Tool, cache: UsageCache) {
+        for (profile, entry) in &cache {
+            let errors = self
+                .fetch_errors
+                .entry((tool.id(), profile.clone()))
+                .or_default();
+            if entry.is_stale {
+                *errors += 1;
+            }
+        }
+        let merged = merge_usage_cache(cache, self.usage_caches.get(&tool));
+        self.usage_caches.insert(tool, merged);
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let inner: Vec<String> = pairs
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape_label(v)))
+        .collect();
+    format!("{{{}}}", inner.join(","))
+}
+
+/// Renders the state in the Prometheus text exposition format.
+fn render(state: &MetricsState) -> String {
+    let mut usage = String::new();
+    let mut resets = String::new();
+    let mut limited = String::new();
+    let now = Utc::now();
+
+    for tool in Tool::ALL {
+        let Some(cache) = state.usage_caches.get(&tool) else {
+            continue;
+        };
+        let mut profiles: Vec<&String> = cache.keys().collect();
+        profiles.sort();
+        for profile in profiles {
+            let entry = &cache[profile];
+            for line in &entry.usage_lines {
+                let UsageLine::Data {
+                    label,
+                    percent,
+                    resets_at,
+                } = line
+                else {
+                    continue;
+                };
+                let window_labels =
+                    labels(&[("tool", tool.id()), ("profile", profile), ("window", label)]);
+                let _ = writeln!(usage, "aip_usage_percent{} {}", window_labels, percent);
+                if let Some(resets_at) = resets_at {
+                    let _ = writeln!(
+                        resets,
+                        "aip_window_reset_timestamp_seconds{} {}",
+                        window_labels,
+                        resets_at.timestamp()
+                    );
+                }
+            }
+            let is_limited = entry.rate_limited_until.is_some_and(|until| until > now);
+            let _ = writeln!(
+                limited,
+                "aip_rate_limited{} {}",
+                labels(&[("tool", tool.id()), ("profile", profile)]),
+                u8::from(is_limited)
+            );
+        }
+    }
+
+    let mut errors = String::new();
+    for ((tool, profile), count) in &state.fetch_errors {
+        let _ = writeln!(
+            errors,
+            "aip_fetch_errors_total{} {}",
+            labels(&[("tool", tool), ("profile", profile)]),
+            count
+        );
+    }
+
+    let mut out = String::new();
+    for (name, kind, help, samples) in [
+        (
+            "aip_usage_percent",
+            "gauge",
+            "Used percentage of a usage window.",
+            usage,
+        ),
+        (
+            "aip_window_reset_timestamp_seconds",
+            "gauge",
+            "Unix time at which a usage window resets.",
+            resets,
+        ),
+        (
+            "aip_fetch_errors_total",
+            "counter",
+            "Usage fetches that failed since the exporter started.",
+            errors,
+        ),
+        (
+            "aip_rate_limited",
+            "gauge",
+            "1 while the usage API rate-limits the profile.",
+            limited,
+        ),
+    ] {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        out.push_str(&samples);
+    }
+    out
+}
+
+/// Builds the HTTP response for a raw request; only `GET /metrics` is served.
+fn respond(request: &str, metrics: &str) -> String {
+    let mut parts = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let (status, content_type, body) = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/metrics")) => (
+            "200 OK",
+            "text/plain; version=0.0.4; charset=utf-8",
+            metrics,
+        ),
+        (Some("GET"), Some(_)) => ("404 Not Found", "text/plain", "Not found; see /metrics\n"),
+        _ => (
+            "405 Method Not Allowed",
+            "text/plain",
+            "Method not allowed\n",
+        ),
+    };
+    format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )
+}
+
+/// Reads request headers, or `None` when the client stalls past `timeout`.
+async fn read_request<R: AsyncRead + Unpin>(stream: &mut R, timeout: Duration) -> Option<Vec<u8>> {
+    let read = async {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 1024];
+        while !buf.windows(4).any(|w| w == b"\r\n\r\n") && buf.len() < MAX_REQUEST_BYTES {
+            match stream.read(&mut chunk).await {
+                Ok(0) | Err(_) => break,
+                Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            }
+        }
+        buf
+    };
+    tokio::time::timeout(timeout, read).await.ok()
+}
+
+async fn handle_connection(mut stream: TcpStream, rendered: Arc<RwLock<String>>) {
+    let Some(buf) = read_request(&mut stream, REQUEST_TIMEOUT).await else {
+        return;
+    };
+    let request = String::from_utf8_lossy(&buf);
+    let response = respond(&request, &rendered.read().await);
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+async fn serve(listener: TcpListener, rendered: Arc<RwLock<String>>) {
+    loop {
+        if let Ok((stream, _)) = listener.accept().await {
+            tokio::spawn(handle_connection(stream, rendered.clone()));
+        }
+    }
+}
+
+fn write_textfile(path: &Path, metrics: &str) -> Result<()> {
+    atomic_write(path, metrics).with_context(|| format!("failed to write {}", path.display()))
+}
+
+pub async fn cmd_serve_metrics(
+    listen: Option<SocketAddr>,
+    textfile: Option<PathBuf>,
+    interval_secs: Option<u64>,
+) -> Result<()> {
+    let interval = match interval_secs {
+        Some(0) => bail!("--interval must be greater than zero"),
+        Some(secs) => Duration::from_secs(secs),
+        None => Config::load()
+            .refresh_interval()
+            .unwrap_or(DEFAULT_INTERVAL),
+    };
+    // Serve on the default address unless only a textfile was requested.
+    let listen = match (listen, &textfile) {
+        (Some(addr), _) => Some(addr),
+        (None, None) => Some(DEFAULT_LISTEN.parse()?),
+        (None, Some(_)) => None,
+    };
+
+    let rendered = Arc::new(RwLock::new(render(&MetricsState::default())));
+    if let Some(addr) = listen {
+        let listener = TcpListener::bind(addr)
+            .await
+            .with_context(|| format!("failed to listen on {}", addr))?;
+        println!("Serving metrics on http://{}/metrics", addr);
+        tokio::spawn(serve(listener, rendered.clone()));
+    }
+    if let Some(path) = &textfile {
+        println!("Writing metrics to {}", path.display());
+    }
+
+    let mut state = MetricsState::default();
+    let mut ticker = tokio::time::interval(interval);
+    let ctrl_c_future = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c_future);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = &mut ctrl_c_future => return Ok(()),
+        }
+
+        for tool in Tool::ALL {
+            if rate_limited_until(state.usage_caches.get(&tool), Utc::now()).is_some() {
+                continue;
+            }
+            let profiles = tool.list_profiles().unwrap_or_default();
+            if profiles.is_empty() {
+                continue;
+            }
+            let cache = match tool {
+                Tool::Claude => prefetch_claude_usage(&profiles).await,
+                Tool::Codex => prefetch_codex_usage(&profiles).await,
+            };
+            state.update(tool, cache);
+        }
+
+        let metrics = render(&state);
+        if let Some(path) = &textfile
+            && let Err(e) = write_textfile(path, &metrics)
+        {
+            eprintln!("Error: {:#}", e);
+        }
+        *rendered.write().await = metrics;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage::ProfileUsageCache;
+
+    fn entry(usage_lines: Vec<UsageLine>, is_stale: bool) -> ProfileUsageCache {
+        ProfileUsageCache {
+            usage_lines,
+            plan_type: None,
+            is_stale,
+            rate_limited_until: None,
+        }
+    }
+
+    #[test]
+    fn render_exposes_usage_resets_errors_and_rate_limits() {
+        let mut state = MetricsState::default();
+        state.update(
+            Tool::Claude,
+            UsageCache::from([(
+                "work".to_string(),
+                entry(
+                    vec![UsageLine::Data {
+                        label: "5-hour".to_string(),
+                        percent: 42.5,
+                        resets_at: Some("2026-01-01T00:00:00Z".parse().unwrap()),
+                    }],
+                    false,
+                ),
+            )]),
+        );
+        state.update(
+            Tool::Claude,
+            UsageCache::from([(
+                "work".to_string(),
+                entry(vec![UsageLine::Text("Error: boom".to_string())], true),
+            )]),
+        );
+
+        let text = render(&state);
+
+        assert!(text.contains("# TYPE aip_usage_percent gauge\n"));
+        assert!(text.contains(
+            "aip_usage_percent{tool=\"claude\",profile=\"work\",window=\"5-hour\"} 42.5\n"
+        ));
+        assert!(text.contains(
+            "aip_window_reset_timestamp_seconds{tool=\"claude\",profile=\"work\",window=\"5-hour\"} 1767225600\n"
+        ));
+        assert!(text.contains("aip_fetch_errors_total{tool=\"claude\",profile=\"work\"} 1\n"));
+        assert!(text.contains("aip_rate_limited{tool=\"claude\",profile=\"work\"} 0\n"));
+    }
+
+    #[test]
+    fn labels_are_escaped() {
+        assert_eq!(
+            labels(&[("profile", "a\"b\\c\nd")]),
+            "{profile=\"a\\\"b\\\\c\\nd\"}"
+        );
+    }
+
+    #[test]
+    fn respond_serves_metrics_path_only() {
+        let ok = respond("GET /metrics HTTP/1.1\r\nHost: x\r\n\r\n", "m 1\n");
+        assert!(ok.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(ok.contains("Content-Length: 4\r\n"));
+        assert!(ok.ends_with("\r\n\r\nm 1\n"));
+
+        assert!(respond("GET / HTTP/1.1\r\n\r\n", "").starts_with("HTTP/1.1 404"));
+        assert!(respond("POST /metrics HTTP/1.1\r\n\r\n", "").starts_with("HTTP/1.1 405"));
+    }
+
+    #[test]
+    fn serves_metrics_over_tcp() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let rendered = Arc::new(RwLock::new("aip_rate_limited 0\n".to_string()));
+            tokio::spawn(serve(listener, rendered));
+
+            let mut stream = TcpStream::connect(addr).await.unwrap();
+            stream
+                .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+                .await
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+
+            assert!(response.starts_with("HTTP/1.1 200 OK"));
+            assert!(response.ends_with("aip_rate_limited 0\n"));
+        });
+    }
+
+    #[test]
+    fn write_textfile_replaces_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("aip.prom");
+
+        write_textfile(&path, "old\n").unwrap();
+        write_textfile(&path, "new\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new\n");
+    }
+
+    #[test]
+    fn read_request_gives_up_on_a_stalled_client() {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        rt.block_on(async {
+            let (mut client, mut server) = tokio::io::duplex(64);
+            client
+                .write_all(b"GET /metrics HTTP/1.1\r\n")
+                .await
+                .unwrap();
+            let stalled = read_request(&mut server, Duration::from_millis(50)).await;
+            assert!(stalled.is_none());
+
+            let (mut client, mut server) = tokio::io::duplex(64);
+            client
+                .write_all(b"GET /metrics HTTP/1.1\r\n\r\n")
+                .await
+                .unwrap();
+            let request = read_request(&mut server, Duration::from_millis(50)).await;
+            assert_eq!(
+                request.as_deref(),
+                Some(&b"GET /metrics HTTP/1.1\r\n\r\n"[..])
+            );
+        });
+    }
+}
@@ -0,0 +1,564 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+
+use crate::config;
+use crate::fs_util::{FileLock, atomic_write};
+use crate::tool::Tool;
+use crate::usage::{UsageCache, UsageLine};
+
+/// How far `resets_at` may move between fetches of the same window.
+///
+/// Codex reports a relative reset, so its absolute time moves by a few seconds.
+const RESET_DRIFT: TimeDelta = TimeDelta::minutes(1);
+
+/// How long to wait for another process deciding which hooks to fire.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A user-defined hook that runs when a usage window crosses a threshold or resets.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct NotificationRule {
+    /// `claude` or `codex`; any tool when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    /// Profile name glob supporting `*` and `?`; any profile when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Window label such as `5-hour` or `Weekly`; any window when omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window: Option<String>,
+    /// Fires once per window when its used percentage reaches this value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    /// Fires when a window resets.
+    #[serde(default)]
+    pub on_reset: bool,
+    /// Shell command run with the event as JSON on stdin.
+    pub command: String,
+}
+
+impl NotificationRule {
+    fn matches(&self, tool: Tool, profile: &str, window: &str) -> bool {
+        self.tool.as_deref().is_none_or(|t| t == tool.id())
+            && self
+                .profile
+                .as_deref()
+                .is_none_or(|p| glob_match(p, profile))
+            && self.window.as_deref().is_none_or(|w| w == window)
+    }
+}
+
+/// Matches `text` against a glob with `*` (any run) and `?` (any one character).
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    t = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Trigger {
+    Threshold,
+    Reset,
+}
+
+/// The payload passed to a hook command.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Notification {
+    pub event: Trigger,
+    pub tool: &'static str,
+    pub profile: String,
+    pub window: String,
+    pub used_percent: f64,
+    pub threshold: Option<f64>,
+    pub resets_at: Option<DateTime<Utc>>,
+    pub at: DateTime<Utc>,
+    #[serde(skip)]
+    pub command: String,
+}
+
+impl Notification {
+    fn env(&self) -> Vec<(&'static str, String)> {
+        let event = match self.event {
+            Trigger::Threshold => "threshold",
+            Trigger::Reset => "reset",
+        };
+        vec![
+            ("AIP_EVENT", event.to_string()),
+            ("AIP_TOOL", self.tool.to_string()),
+            ("AIP_PROFILE", self.profile.clone()),
+            ("AIP_WINDOW", self.window.clone()),
+            ("AIP_USED_PERCENT", format!("{:.1}", self.used_percent)),
+            (
+                "AIP_THRESHOLD",
+                self.threshold.map(|t| t.to_string()).unwrap_or_default(),
+            ),
+            (
+                "AIP_RESETS_AT",
+                self.resets_at.map(|r| r.to_rfc3339()).unwrap_or_default(),
+            ),
+        ]
+    }
+}
+
+fn same_window(a: Option<DateTime<Utc>>, b: Option<DateTime<Utc>>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => (a - b).abs() <= RESET_DRIFT,
+        (a, b) => a == b,
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Reading {
+    percent: f64,
+    resets_at: Option<DateTime<Utc>>,
+}
+
+/// A window reset between two readings when its reset time moved on, or when
+/// usage fell to under half; smaller drops are rounding or late-arriving
+/// corrections.
+pub fn did_reset(
+    previous_percent: f64,
+    previous_resets_at: Option<DateTime<Utc>>,
+    percent: f64,
+    resets_at: Option<DateTime<Utc>>,
+) -> bool {
+    !same_window(previous_resets_at, resets_at) || percent < previous_percent / 2.0
+}
+
+fn load_state(path: &Path) -> HashMap<String, Option<DateTime<Utc>>> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Identifies a threshold rule by what it does rather than its position, so
+/// reordering rules does not fire them again.
+fn fired_key(
+    rule: &NotificationRule,
+    threshold: f64,
+    tool: Tool,
+    profile: &str,
+    window: &str,
+) -> String {
+    format!(
+        "{}/{}/{}/{}/{}",
+        tool.id(),
+        profile,
+        window,
+        threshold,
+        rule.command
+    )
+}
+
+/// Evaluates notification rules against fetched usage, firing each rule at
+/// most once per usage window.
+pub struct Notifier {
+    rules: Vec<NotificationRule>,
+    /// Window (by reset time) each threshold rule last fired for, persisted across runs.
+    fired: HashMap<String, Option<DateTime<Utc>>>,
+    state_path: Option<PathBuf>,
+    last_seen: HashMap<(Tool, String, String), Reading>,
+}
+
+impl Notifier {
+    fn new(rules: Vec<NotificationRule>, state_path: Option<PathBuf>) -> Self {
+        let fired = state_path.as_deref().map(load_state).unwrap_or_default();
+        Self {
+            rules,
+            fired,
+            state_path,
+            last_seen: HashMap::new(),
+        }
+    }
+
+    /// A notifier for the rules in aip's config, or `None` when there are none.
+    pub fn from_config(config: &config::Config) -> Option<Self> {
+        if config.notifications.is_empty() {
+            return None;
+        }
+        let state_path = config::aip_dir()
+            .ok()
+            .map(|dir| dir.join("notify-state.json"));
+        Some(Self::new(config.notifications.clone(), state_path))
+    }
+
+    /// Locks the state file and merges in windows other processes fired for.
+    ///
+    /// Lock failures fall back to the in-memory state rather than dropping hooks.
+    fn lock_state(&mut self) -> Option<FileLock> {
+        let path = self.state_path.as_ref()?;
+        if let Some(parent) = path.parent() {
+            let _ = std::fs::create_dir_all(parent);
+        }
+        let lock = FileLock::acquire(&path.with_extension("json.lock"), LOCK_TIMEOUT).ok();
+        self.fired.extend(load_state(path));
+        lock
+    }
+
+    fn save_state(&self) -> Result<()> {
+        let Some(path) = &self.state_path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        atomic_write(path, &serde_json::to_string_pretty(&self.fired)?)
+    }
+
+    /// Notifications due for the fresh entries of `cache`.
+    pub fn evaluate(
+        &mut self,
+        tool: Tool,
+        cache: &UsageCache,
+        now: DateTime<Utc>,
+    ) -> Vec<Notification> {
+        let mut readings: Vec<(&String, &String, Reading)> = Vec::new();
+        for (profile, entry) in cache.iter().filter(|(_, e)| !e.is_stale) {
+            for line in &entry.usage_lines {
+                if let UsageLine::Data {
+                    label,
+                    percent,
+                    resets_at,
+                } = line
+                {
+                    readings.push((
+                        profile,
+                        label,
+                        Reading {
+                            percent: *percent,
+                            resets_at: *resets_at,
+                        },
+                    ));
+                }
+            }
+        }
+        readings.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+        let mut due = Vec::new();
+        let mut crossed = Vec::new();
+        for (profile, window, reading) in readings {
+            let key = (tool, profile.clone(), window.clone());
+            let previous = self.last_seen.insert(key, reading);
+            let did_reset = previous.is_some_and(|p| {
+                did_reset(p.percent, p.resets_at, reading.percent, reading.resets_at)
+            });
+
+            for rule in &self.rules {
+                if !rule.matches(tool, profile, window) {
+                    continue;
+                }
+                let notification = |event, threshold| Notification {
+                    event,
+                    tool: tool.id(),
+                    profile: profile.clone(),
+                    window: window.clone(),
+                    used_percent: reading.percent,
+                    threshold,
+                    resets_at: reading.resets_at,
+                    at: now,
+                    command: rule.command.clone(),
+                };
+                if rule.on_reset && did_reset {
+                    due.push(notification(Trigger::Reset, None));
+                }
+                if let Some(threshold) = rule.threshold
+                    && reading.percent >= threshold
+                {
+                    crossed.push((
+                        fired_key(rule, threshold, tool, profile, window),
+                        notification(Trigger::Threshold, Some(threshold)),
+                    ));
+                }
+            }
+        }
+        if crossed.is_empty() {
+            return due;
+        }
+
+        // Another aip process may watch the same profiles, so decide under the lock.
+        let _lock = self.lock_state();
+        let mut fired_changed = false;
+        for (key, notification) in crossed {
+            let already = self
+                .fired
+                .get(&key)
+                .is_some_and(|w| same_window(*w, notification.resets_at));
+            if !already {
+                self.fired.insert(key, notification.resets_at);
+                fired_changed = true;
+                due.push(notification);
+            }
+        }
+        if fired_changed {
+            let _ = self.save_state();
+        }
+        due
+    }
+}
+
+/// Runs a hook with the notification as JSON on stdin and `AIP_*` env vars.
+pub async fn run_hook(notification: &Notification) -> Result<std::process::ExitStatus> {
+    let payload = serde_json::to_string(notification)?;
+    let mut child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(&notification.command)
+        .envs(notification.env())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // The hook may exit without reading stdin.
+        let _ = stdin.write_all(payload.as_bytes()).await;
+    }
+    Ok(child.wait().await?)
+}
+
+/// Runs hooks in the background so a slow command never stalls a fetch loop.
+pub fn spawn_hooks(notifications: Vec<Notification>) {
+    for notification in notifications {
+        tokio::spawn(async move {
+            match run_hook(&notification).await {
+                Ok(status) if !status.success() => eprintln!(
+                    "Warning: notification hook '{}' exited with {}",
+                    notification.command, status
+                ),
+                Ok(_) => {}
+                Err(e) => eprintln!(
+                    "Warning: failed to run notification hook '{}': {}",
+                    notification.command, e
+                ),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::usage::ProfileUsageCache;
+
+    fn state_path_in(dir: &Path) -> Option<PathBuf> {
+        Some(dir.join("notify-state.json"))
+    }
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn cache(profile: &str, percent: f64, resets_at: &str) -> UsageCache {
+        UsageCache::from([(
+            profile.to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Data {
+                    label: "5-hour".to_string(),
+                    percent,
+                    resets_at: Some(at(resets_at)),
+                }],
+                plan_type: None,
+                is_stale: false,
+                rate_limited_until: None,
+            },
+        )])
+    }
+
+    fn rule(threshold: Option<f64>, on_reset: bool) -> NotificationRule {
+        NotificationRule {
+            threshold,
+            on_reset,
+            command: "true".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn glob_match_supports_star_and_question_mark() {
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("work-*", "work-team"));
+        assert!(glob_match("w?rk", "work"));
+        assert!(glob_match("*-max-*", "acme-max-2"));
+        assert!(!glob_match("work-*", "personal"));
+        assert!(!glob_match("w?rk", "wrk"));
+    }
+
+    #[test]
+    fn rule_matches_tool_profile_and_window() {
+        let rule = NotificationRule {
+            tool: Some("claude".to_string()),
+            profile: Some("team-*".to_string()),
+            window: Some("Weekly".to_string()),
+            ..rule(Some(80.0), false)
+        };
+
+        assert!(rule.matches(Tool::Claude, "team-a", "Weekly"));
+        assert!(!rule.matches(Tool::Codex, "team-a", "Weekly"));
+        assert!(!rule.matches(Tool::Claude, "solo", "Weekly"));
+        assert!(!rule.matches(Tool::Claude, "team-a", "5-hour"));
+    }
+
+    #[test]
+    fn threshold_fires_once_per_window() {
+        let mut notifier = Notifier::new(vec![rule(Some(80.0), false)], None);
+        let now = Utc::now();
+
+        let below = notifier.evaluate(Tool::Claude, &cache("a", 70.0, "2026-01-01T05:00:00Z"), now);
+        let first = notifier.evaluate(Tool::Claude, &cache("a", 85.0, "2026-01-01T05:00:00Z"), now);
+        let again = notifier.evaluate(Tool::Claude, &cache("a", 90.0, "2026-01-01T05:00:30Z"), now);
+        let next_window =
+            notifier.evaluate(Tool::Claude, &cache("a", 81.0, "2026-01-01T10:00:00Z"), now);
+
+        assert!(below.is_empty());
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].event, Trigger::Threshold);
+        assert_eq!(first[0].threshold, Some(80.0));
+        assert!(again.is_empty());
+        assert_eq!(next_window.len(), 1);
+    }
+
+    #[test]
+    fn fired_windows_persist_across_notifiers() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = Utc::now();
+
+        let mut notifier = Notifier::new(vec![rule(Some(80.0), false)], state_path_in(dir.path()));
+        let first = notifier.evaluate(Tool::Codex, &cache("a", 85.0, "2026-01-01T05:00:00Z"), now);
+        let mut restarted = Notifier::new(vec![rule(Some(80.0), false)], state_path_in(dir.path()));
+        let after_restart =
+            restarted.evaluate(Tool::Codex, &cache("a", 86.0, "2026-01-01T05:00:00Z"), now);
+
+        assert_eq!(first.len(), 1);
+        assert!(after_restart.is_empty());
+    }
+
+    #[test]
+    fn concurrent_notifiers_fire_a_threshold_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = Utc::now();
+        let mut dashboard = Notifier::new(vec![rule(Some(80.0), false)], state_path_in(dir.path()));
+        let mut watch = Notifier::new(vec![rule(Some(80.0), false)], state_path_in(dir.path()));
+
+        let first =
+            dashboard.evaluate(Tool::Claude, &cache("a", 85.0, "2026-01-01T05:00:00Z"), now);
+        let second = watch.evaluate(Tool::Claude, &cache("a", 86.0, "2026-01-01T05:00:00Z"), now);
+
+        assert_eq!(first.len(), 1);
+        assert!(second.is_empty());
+    }
+
+    #[test]
+    fn reordering_rules_does_not_fire_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let now = Utc::now();
+        let other = NotificationRule {
+            command: "echo other".to_string(),
+            ..rule(Some(95.0), false)
+        };
+
+        let mut notifier = Notifier::new(
+            vec![rule(Some(80.0), false), other.clone()],
+            state_path_in(dir.path()),
+        );
+        let first = notifier.evaluate(Tool::Claude, &cache("a", 85.0, "2026-01-01T05:00:00Z"), now);
+        let mut reordered = Notifier::new(
+            vec![other, rule(Some(80.0), false)],
+            state_path_in(dir.path()),
+        );
+        let after =
+            reordered.evaluate(Tool::Claude, &cache("a", 86.0, "2026-01-01T05:00:00Z"), now);
+
+        assert_eq!(first.len(), 1);
+        assert!(after.is_empty());
+    }
+
+    #[test]
+    fn reset_fires_when_window_moves() {
+        let mut notifier = Notifier::new(vec![rule(None, true)], None);
+        let now = Utc::now();
+
+        let first = notifier.evaluate(Tool::Claude, &cache("a", 90.0, "2026-01-01T05:00:00Z"), now);
+        let same = notifier.evaluate(Tool::Claude, &cache("a", 95.0, "2026-01-01T05:00:00Z"), now);
+        let reset = notifier.evaluate(Tool::Claude, &cache("a", 0.0, "2026-01-01T10:00:00Z"), now);
+
+        assert!(first.is_empty());
+        assert!(same.is_empty());
+        assert_eq!(reset.len(), 1);
+        assert_eq!(reset[0].event, Trigger::Reset);
+    }
+
+    #[test]
+    fn small_drop_within_a_window_is_not_a_reset() {
+        let mut notifier = Notifier::new(vec![rule(None, true)], None);
+        let now = Utc::now();
+
+        notifier.evaluate(Tool::Claude, &cache("a", 90.0, "2026-01-01T05:00:00Z"), now);
+        let dip = notifier.evaluate(Tool::Claude, &cache("a", 89.0, "2026-01-01T05:00:30Z"), now);
+        let reset = notifier.evaluate(Tool::Claude, &cache("a", 10.0, "2026-01-01T05:00:30Z"), now);
+
+        assert!(dip.is_empty());
+        assert_eq!(reset.len(), 1);
+    }
+
+    #[test]
+    fn run_hook_passes_json_on_stdin_and_env_vars() {
+        let dir = tempfile::tempdir().unwrap();
+        let out = dir.path().join("out");
+        let notification = Notification {
+            event: Trigger::Threshold,
+            tool: "claude",
+            profile: "work".to_string(),
+            window: "Weekly".to_string(),
+            used_percent: 82.0,
+            threshold: Some(80.0),
+            resets_at: None,
+            at: at("2026-01-01T00:00:00Z"),
+            command: format!(
+                "cat > '{0}'; echo \"$AIP_PROFILE $AIP_WINDOW $AIP_THRESHOLD\" >> '{0}'",
+                out.display()
+            ),
+        };
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let status = rt.block_on(run_hook(&notification)).unwrap();
+
+        assert!(status.success());
+        let written = std::fs::read_to_string(&out).unwrap();
+        let (json, env) = written.split_once('}').unwrap();
+        let payload: serde_json::Value = serde_json::from_str(&format!("{}}}", json)).unwrap();
+        assert_eq!(payload["event"], "threshold");
+        assert_eq!(payload["profile"], "work");
+        assert_eq!(payload["used_percent"], 82.0);
+        assert_eq!(env.trim(), "work Weekly 80");
+    }
+}
@@ -0,0 +1,754 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::credential_store::CredentialStore;
+use crate::meta::ProfileMeta;
+use crate::tool::{self, Tool};
+use crate::{backup, claude, codex, fs_util};
+
+/// A switch failure that scripts may want to tell apart by exit code.
+#[derive(Debug)]
+pub enum SwitchError {
+    NotFound(String),
+    RefreshFailed {
+        profile: String,
+        source: anyhow::Error,
+    },
+    CredentialWrite(anyhow::Error),
+}
+
+impl SwitchError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            SwitchError::NotFound(_) => 3,
+            SwitchError::RefreshFailed { .. } => 4,
+            SwitchError::CredentialWrite(_) => 5,
+        }
+    }
+}
+
+impl std::fmt::Display for SwitchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SwitchError::NotFound(msg) => write!(f, "{}", msg),
+            SwitchError::RefreshFailed { profile, source } => {
+                write!(f, "token refresh failed for '{}': {}", profile, source)
+            }
+            SwitchError::CredentialWrite(source) => {
+                write!(f, "failed to write active credentials: {}", source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SwitchError {}
+
+const JOURNAL_FILE: &str = ".switch-journal.json";
+
+/// Write-ahead record of a switch in progress, removed once it completes.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct SwitchJournal {
+    /// Raw `_current` content before the switch.
+    previous: Option<String>,
+    to: String,
+    started_at: DateTime<Utc>,
+}
+
+/// What recovery did with a switch left unfinished by a killed aip.
+#[derive(Debug, PartialEq)]
+pub enum Recovery {
+    Completed(String),
+    RolledBack(String),
+}
+
+/// Decides whether the active credentials may overwrite a stored profile.
+///
+/// Called with the profile name, the active credentials and the profile's
+/// currently stored credentials (if any).
+pub type SyncGuard = Box<dyn Fn(&str, &str, Option<&str>) -> bool + Send>;
+
+/// Switch/save/sync logic shared by all tools, parameterized over where
+/// credentials are stored.
+pub struct ProfileManager {
+    tool: Tool,
+    profiles_dir: PathBuf,
+    store: Box<dyn CredentialStore + Send>,
+    sync_guard: SyncGuard,
+}
+
+impl ProfileManager {
+    pub fn new(tool: Tool, profiles_dir: PathBuf, store: Box<dyn CredentialStore + Send>) -> Self {
+        Self {
+            tool,
+            profiles_dir,
+            store,
+            sync_guard: Box::new(|_, _, _| true),
+        }
+    }
+
+    pub fn for_tool(tool: Tool, store: Box<dyn CredentialStore + Send>) -> Result<Self> {
+        Ok(Self::new(tool, tool.profiles_dir()?, store))
+    }
+
+    pub fn with_sync_guard(
+        mut self,
+        guard: impl Fn(&str, &str, Option<&str>) -> bool + Send + 'static,
+    ) -> Self {
+        self.sync_guard = Box::new(guard);
+        self
+    }
+
+    fn current_file(&self) -> PathBuf {
+        self.profiles_dir.join("_current")
+    }
+
+    fn journal_file(&self) -> PathBuf {
+        self.profiles_dir.join(JOURNAL_FILE)
+    }
+
+    fn profile_dir(&self, name: &str) -> Result<PathBuf> {
+        tool::validate_profile_name(name)?;
+        Ok(self.profiles_dir.join(name))
+    }
+
+    pub fn current_profile(&self) -> Option<String> {
+        tool::read_current_marker(&self.current_file())
+            .ok()
+            .flatten()
+    }
+
+    pub fn switch(&self, profile: &str) -> Result<()> {
+        let profile_dir = self.profile_dir(profile)?;
+        if !profile_dir.exists() {
+            return Err(SwitchError::NotFound(format!(
+                "profile '{}' does not exist for {}",
+                profile, self.tool
+            ))
+            .into());
+        }
+
+        // Save active credentials to current profile
+        self.sync_active_to_current_profile();
+
+        // Load new profile's credentials before making changes
+        let data = self.store.read_profile(&profile_dir)?.ok_or_else(|| {
+            SwitchError::NotFound(format!(
+                "credentials file not found for profile '{}' ({})",
+                profile, self.tool
+            ))
+        })?;
+
+        // Journal the switch, update _current, then write the active credentials.
+        // If the write fails, roll back _current to avoid contamination; if aip
+        // is killed midway, `recover` finishes the switch on the next start.
+        let current_file = self.current_file();
+        let old_current = fs::read_to_string(&current_file).ok();
+        let journal = SwitchJournal {
+            previous: old_current.clone(),
+            to: profile.to_string(),
+            started_at: Utc::now(),
+        };
+        fs_util::atomic_write(&self.journal_file(), &serde_json::to_string(&journal)?)?;
+
+        if let Err(e) = fs_util::atomic_write(&current_file, &format!("{}\n", profile)) {
+            let _ = fs::remove_file(self.journal_file());
+            return Err(e);
+        }
+
+        if let Err(e) = self.store.write_active(&data) {
+            restore_marker(&current_file, old_current.as_deref());
+            let _ = fs::remove_file(self.journal_file());
+            return Err(SwitchError::CredentialWrite(e).into());
+        }
+        let _ = fs::remove_file(self.journal_file());
+
+        let _ = ProfileMeta::update(&profile_dir, |m| {
+            m.last_switched_at = Some(Utc::now());
+            m.switch_reason = None;
+        });
+
+        Ok(())
+    }
+
+    pub fn has_pending_switch(&self) -> bool {
+        self.journal_file().exists()
+    }
+
+    /// Completes a journaled switch whose target credentials are still
+    /// readable, and otherwise rolls back to the previous profile.
+    pub fn recover(&self) -> Result<Option<Recovery>> {
+        let journal_file = self.journal_file();
+        let Ok(content) = fs::read_to_string(&journal_file) else {
+            return Ok(None);
+        };
+        let Ok(journal) = serde_json::from_str::<SwitchJournal>(&content) else {
+            // A torn journal means the switch never touched anything else.
+            fs::remove_file(&journal_file)?;
+            return Ok(None);
+        };
+
+        let current_file = self.current_file();
+        let target = self.profile_dir(&journal.to).ok();
+        let data = target
+            .as_deref()
+            .and_then(|dir| self.store.read_profile(dir).ok().flatten());
+        let recovery = match (target, data) {
+            (Some(target), Some(data)) => {
+                fs_util::atomic_write(&current_file, &format!("{}\n", journal.to))?;
+                self.store.write_active(&data)?;
+                let _ = ProfileMeta::update(&target, |m| {
+                    m.last_switched_at = Some(journal.started_at);
+                    m.switch_reason = None;
+                });
+                Recovery::Completed(journal.to)
+            }
+            _ => {
+                restore_marker(&current_file, journal.previous.as_deref());
+                if let Some(previous) = self.current_profile()
+                    && let Some(data) = self.store.read_profile(&self.profile_dir(&previous)?)?
+                {
+                    self.store.write_active(&data)?;
+                }
+                Recovery::RolledBack(journal.to)
+            }
+        };
+        fs::remove_file(&journal_file)?;
+        Ok(Some(recovery))
+    }
+
+    pub fn sync_active_to_current_profile(&self) {
+        if let Some(current) = self.current_profile() {
+            self.sync_active_to_profile(&current);
+        }
+    }
+
+    /// Copies the active credentials back into `profile` if they changed.
+    pub fn sync_active_to_profile(&self, profile: &str) {
+        self.sync_to_profile(profile, None);
+    }
+
+    /// Like `sync_active_to_profile`, but keeps the profile's credentials when
+    /// they no longer match `activated`, the data `activate` copied out of it.
+    pub fn sync_active_to_unchanged_profile(&self, profile: &str, activated: &str) {
+        self.sync_to_profile(profile, Some(activated));
+    }
+
+    fn sync_to_profile(&self, profile: &str, activated: Option<&str>) {
+        let Ok(dest_dir) = self.profile_dir(profile) else {
+            return;
+        };
+        let data = match self.store.read_active() {
+            Ok(Some(d)) => d,
+            _ => return,
+        };
+        let stored = self.store.read_profile(&dest_dir).ok().flatten();
+        if stored.as_deref() == Some(data.as_str()) {
+            return;
+        }
+        if let Some(activated) = activated
+            && stored.as_deref() != Some(activated)
+        {
+            eprintln!(
+                "Warning: profile '{}' changed while the command ran; keeping its credentials",
+                profile
+            );
+            return;
+        }
+        if !(self.sync_guard)(profile, &data, stored.as_deref()) {
+            return;
+        }
+        if let Some(stored) = &stored
+            && let Err(e) = backup::record(&dest_dir, stored)
+        {
+            eprintln!(
+                "Warning: skipping sync to profile '{}'; backing up its credentials failed: {}",
+                profile, e
+            );
+            return;
+        }
+        if let Err(e) = self.store.write_profile(&dest_dir, &data) {
+            eprintln!(
+                "Warning: failed to sync credentials to profile '{}': {}",
+                profile, e
+            );
+        }
+    }
+
+    /// Writes `profile`'s stored credentials as the active ones without
+    /// touching `_current`.
+    pub fn activate(&self, profile: &str) -> Result<String> {
+        let profile_dir = self.profile_dir(profile)?;
+        let data = self.store.read_profile(&profile_dir)?.ok_or_else(|| {
+            SwitchError::NotFound(format!(
+                "credentials file not found for profile '{}' ({})",
+                profile, self.tool
+            ))
+        })?;
+        self.store.write_active(&data)?;
+        Ok(data)
+    }
+
+    pub fn save(&self, name: &str) -> Result<()> {
+        let dest_dir = self.profile_dir(name)?;
+        let newly_created = !dest_dir.exists();
+        fs::create_dir_all(&dest_dir)?;
+
+        let result = (|| -> Result<()> {
+            if let Some(existing) = self.store.read_profile(&dest_dir)? {
+                backup::record(&dest_dir, &existing)?;
+            }
+            self.store
+                .save_active_to_profile(&dest_dir)
+                .with_context(|| format!("failed to save active credentials for {}", self.tool))?;
+
+            // Update current profile to the newly saved one
+            fs_util::atomic_write(&self.current_file(), &format!("{}\n", name))?;
+
+            let _ = ProfileMeta::update(&dest_dir, |m| {
+                m.created_at.get_or_insert_with(Utc::now);
+            });
+
+            Ok(())
+        })();
+
+        if result.is_err() && newly_created {
+            let _ = fs::remove_dir_all(&dest_dir);
+        }
+
+        result
+    }
+
+    /// Rolls `profile`'s credentials back to backup `version` (1 is the most
+    /// recent), backing up the replaced ones first. Also updates the active
+    /// credentials when `profile` is current.
+    pub fn restore(&self, profile: &str, version: usize) -> Result<()> {
+        let profile_dir = self.profile_dir(profile)?;
+        if !profile_dir.exists() {
+            return Err(SwitchError::NotFound(format!(
+                "profile '{}' does not exist for {}",
+                profile, self.tool
+            ))
+            .into());
+        }
+        let data = backup::read_version(&profile_dir, version)?;
+        if let Some(existing) = self.store.read_profile(&profile_dir)?
+            && existing != data
+        {
+            backup::record(&profile_dir, &existing)?;
+        }
+        self.store.write_profile(&profile_dir, &data)?;
+        if self.current_profile().as_deref() == Some(profile) {
+            self.store
+                .write_active(&data)
+                .map_err(SwitchError::CredentialWrite)?;
+        }
+        Ok(())
+    }
+}
+
+/// Switches `tool` to `profile`, refreshing an expired Claude token first.
+pub async fn switch(tool: Tool, profile: &str) -> Result<()> {
+    if tool == Tool::Claude {
+        // Refresh only non-current profiles before switching.
+        // The current profile's token is managed by Claude Code.
+        let creds_path = Tool::Claude.profile_dir(profile)?.join("credentials.json");
+        let current = Tool::Claude.current_profile().ok().flatten();
+        if current.as_deref() != Some(profile) && creds_path.exists() {
+            claude::usage::refresh_credentials_if_expired(&creds_path)
+                .await
+                .map_err(|source| SwitchError::RefreshFailed {
+                    profile: profile.to_string(),
+                    source,
+                })?;
+        }
+    }
+
+    match tool {
+        Tool::Claude => claude::profile::switch(profile).await,
+        Tool::Codex => {
+            let profile = profile.to_string();
+            tokio::task::spawn_blocking(move || codex::profile::switch(&profile)).await?
+        }
+    }
+}
+
+/// Rolls `tool`'s `profile` back to credential backup `version`.
+pub fn restore(tool: Tool, profile: &str, version: usize) -> Result<()> {
+    match tool {
+        Tool::Claude => claude::profile::restore(profile, version),
+        Tool::Codex => codex::profile::restore(profile, version),
+    }
+}
+
+/// Finishes or rolls back switches that a killed aip left half done.
+pub fn recover_interrupted_switches() {
+    for tool in Tool::ALL {
+        let result = match tool {
+            Tool::Claude => claude::profile::recover_switch(),
+            Tool::Codex => codex::profile::recover_switch(),
+        };
+        match result {
+            Ok(Some(Recovery::Completed(profile))) => eprintln!(
+                "Warning: finished an interrupted switch to '{}' for {}",
+                profile, tool
+            ),
+            Ok(Some(Recovery::RolledBack(profile))) => eprintln!(
+                "Warning: rolled back an interrupted switch to '{}' for {}",
+                profile, tool
+            ),
+            Ok(None) => {}
+            Err(e) => eprintln!(
+                "Warning: failed to recover an interrupted switch for {}: {:#}",
+                tool, e
+            ),
+        }
+    }
+}
+
+/// Restores a `_current` marker to its previous content, removing it if it did not exist.
+fn restore_marker(path: &Path, previous: Option<&str>) {
+    match previous {
+        Some(prev) => {
+            let _ = fs_util::atomic_write(path, prev);
+        }
+        None => {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::credential_store::MemoryStore;
+
+    fn setup(store: MemoryStore) -> (tempfile::TempDir, MemoryStore, ProfileManager) {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ProfileManager::new(
+            Tool::Claude,
+            dir.path().to_path_buf(),
+            Box::new(store.clone()),
+        );
+        (dir, store, manager)
+    }
+
+    fn add_profile(dir: &Path, store: &MemoryStore, name: &str, data: &str) {
+        let profile_dir = dir.join(name);
+        fs::create_dir_all(&profile_dir).unwrap();
+        store.write_profile(&profile_dir, data).unwrap();
+    }
+
+    #[test]
+    fn save_stores_active_credentials_and_marks_current() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("creds-a"));
+
+        manager.save("work").unwrap();
+
+        assert_eq!(
+            store.profile(&dir.path().join("work")).as_deref(),
+            Some("creds-a")
+        );
+        assert_eq!(manager.current_profile().as_deref(), Some("work"));
+        assert!(
+            ProfileMeta::load(&dir.path().join("work"))
+                .created_at
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn save_without_active_credentials_cleans_up_new_dir() {
+        let (dir, _store, manager) = setup(MemoryStore::default());
+
+        assert!(manager.save("work").is_err());
+        assert!(!dir.path().join("work").exists());
+        assert_eq!(manager.current_profile(), None);
+    }
+
+    #[test]
+    fn save_rejects_invalid_name() {
+        let (_dir, _store, manager) = setup(MemoryStore::with_active("creds"));
+
+        assert!(manager.save("../evil").is_err());
+    }
+
+    #[test]
+    fn switch_syncs_old_profile_and_activates_new_one() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("refreshed-a"));
+        add_profile(dir.path(), &store, "a", "creds-a");
+        add_profile(dir.path(), &store, "b", "creds-b");
+        fs::write(dir.path().join("_current"), "a\n").unwrap();
+
+        manager.switch("b").unwrap();
+
+        assert_eq!(
+            store.profile(&dir.path().join("a")).as_deref(),
+            Some("refreshed-a")
+        );
+        assert_eq!(store.active().as_deref(), Some("creds-b"));
+        assert_eq!(manager.current_profile().as_deref(), Some("b"));
+        assert!(
+            ProfileMeta::load(&dir.path().join("b"))
+                .last_switched_at
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn switch_to_missing_profile_fails_without_changes() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("creds-a"));
+        add_profile(dir.path(), &store, "a", "creds-a");
+        fs::write(dir.path().join("_current"), "a\n").unwrap();
+
+        let err = manager.switch("missing").unwrap_err();
+
+        assert!(err.to_string().contains("does not exist"));
+        assert!(matches!(
+            err.downcast_ref::<SwitchError>(),
+            Some(SwitchError::NotFound(_))
+        ));
+        assert_eq!(manager.current_profile().as_deref(), Some("a"));
+        assert_eq!(store.active().as_deref(), Some("creds-a"));
+    }
+
+    #[test]
+    fn switch_fails_when_profile_has_no_credentials() {
+        let (dir, _store, manager) = setup(MemoryStore::with_active("creds-a"));
+        fs::create_dir_all(dir.path().join("empty")).unwrap();
+
+        let err = manager.switch("empty").unwrap_err();
+
+        assert!(err.to_string().contains("credentials file not found"));
+        assert_eq!(manager.current_profile(), None);
+    }
+
+    #[test]
+    fn switch_rolls_back_current_on_credential_write_failure() {
+        let (dir, store, manager) =
+            setup(MemoryStore::with_active("creds-a").failing_active_writes());
+        add_profile(dir.path(), &store, "a", "creds-a");
+        add_profile(dir.path(), &store, "b", "creds-b");
+        fs::write(dir.path().join("_current"), "a\n").unwrap();
+
+        let err = manager.switch("b").unwrap_err();
+
+        assert!(matches!(
+            err.downcast_ref::<SwitchError>(),
+            Some(SwitchError::CredentialWrite(_))
+        ));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("_current")).unwrap(),
+            "a\n"
+        );
+        assert_eq!(store.active().as_deref(), Some("creds-a"));
+    }
+
+    fn write_journal(dir: &Path, previous: Option<&str>, to: &str) {
+        let journal = SwitchJournal {
+            previous: previous.map(str::to_string),
+            to: to.to_string(),
+            started_at: Utc::now(),
+        };
+        fs::write(
+            dir.join(JOURNAL_FILE),
+            serde_json::to_string(&journal).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn switch_leaves_no_journal() {
+        let (dir, store, manager) =
+            setup(MemoryStore::with_active("creds-a").failing_active_writes());
+        add_profile(dir.path(), &store, "b", "creds-b");
+
+        assert!(manager.switch("b").is_err());
+        assert!(!manager.has_pending_switch());
+
+        let (dir, store, manager) = setup(MemoryStore::default());
+        add_profile(dir.path(), &store, "b", "creds-b");
+
+        manager.switch("b").unwrap();
+        assert!(!manager.has_pending_switch());
+    }
+
+    #[test]
+    fn recover_completes_interrupted_switch() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("creds-a"));
+        add_profile(dir.path(), &store, "a", "creds-a");
+        add_profile(dir.path(), &store, "b", "creds-b");
+        // Killed after writing _current but before the active credentials.
+        write_journal(dir.path(), Some("a\n"), "b");
+        fs::write(dir.path().join("_current"), "b\n").unwrap();
+
+        let recovery = manager.recover().unwrap();
+
+        assert_eq!(recovery, Some(Recovery::Completed("b".to_string())));
+        assert_eq!(manager.current_profile().as_deref(), Some("b"));
+        assert_eq!(store.active().as_deref(), Some("creds-b"));
+        assert!(!manager.has_pending_switch());
+        assert_eq!(manager.recover().unwrap(), None);
+    }
+
+    #[test]
+    fn recover_rolls_back_when_target_credentials_are_gone() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("creds-b"));
+        add_profile(dir.path(), &store, "a", "creds-a");
+        write_journal(dir.path(), Some("a\n"), "b");
+        fs::write(dir.path().join("_current"), "b\n").unwrap();
+
+        let recovery = manager.recover().unwrap();
+
+        assert_eq!(recovery, Some(Recovery::RolledBack("b".to_string())));
+        assert_eq!(manager.current_profile().as_deref(), Some("a"));
+        assert_eq!(store.active().as_deref(), Some("creds-a"));
+        assert!(!manager.has_pending_switch());
+    }
+
+    #[test]
+    fn recover_discards_torn_journal() {
+        let (dir, _store, manager) = setup(MemoryStore::default());
+        fs::write(dir.path().join(JOURNAL_FILE), "{\"previous\"").unwrap();
+
+        assert_eq!(manager.recover().unwrap(), None);
+        assert!(!manager.has_pending_switch());
+    }
+
+    #[test]
+    fn switch_removes_current_on_failure_when_no_previous() {
+        let (dir, store, manager) = setup(MemoryStore::default().failing_active_writes());
+        add_profile(dir.path(), &store, "b", "creds-b");
+
+        assert!(manager.switch("b").is_err());
+
+        assert!(!dir.path().join("_current").exists());
+    }
+
+    #[test]
+    fn switch_error_exit_codes_are_distinct() {
+        let codes = [
+            SwitchError::NotFound(String::new()).exit_code(),
+            SwitchError::RefreshFailed {
+                profile: String::new(),
+                source: anyhow!("expired"),
+            }
+            .exit_code(),
+            SwitchError::CredentialWrite(anyhow!("denied")).exit_code(),
+        ];
+        assert_eq!(codes, [3, 4, 5]);
+    }
+
+    #[test]
+    fn sync_respects_guard() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("other-account"));
+        let manager = manager.with_sync_guard(|_, _, _| false);
+        add_profile(dir.path(), &store, "a", "creds-a");
+        fs::write(dir.path().join("_current"), "a\n").unwrap();
+
+        manager.sync_active_to_current_profile();
+
+        assert_eq!(
+            store.profile(&dir.path().join("a")).as_deref(),
+            Some("creds-a")
+        );
+    }
+
+    #[test]
+    fn activate_writes_active_without_marking_current() {
+        let (dir, store, manager) = setup(MemoryStore::default());
+        add_profile(dir.path(), &store, "a", "creds-a");
+
+        assert_eq!(manager.activate("a").unwrap(), "creds-a");
+
+        assert_eq!(store.active().as_deref(), Some("creds-a"));
+        assert_eq!(manager.current_profile(), None);
+    }
+
+    #[test]
+    fn sync_to_unchanged_profile_keeps_credentials_changed_meanwhile() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("refreshed-a"));
+        add_profile(dir.path(), &store, "a", "saved-again-a");
+
+        manager.sync_active_to_unchanged_profile("a", "creds-a");
+
+        assert_eq!(
+            store.profile(&dir.path().join("a")).as_deref(),
+            Some("saved-again-a")
+        );
+
+        manager.sync_active_to_unchanged_profile("a", "saved-again-a");
+
+        assert_eq!(
+            store.profile(&dir.path().join("a")).as_deref(),
+            Some("refreshed-a")
+        );
+    }
+
+    #[test]
+    fn sync_active_to_profile_writes_changed_credentials() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("refreshed-a"));
+        add_profile(dir.path(), &store, "a", "creds-a");
+
+        manager.sync_active_to_profile("a");
+
+        assert_eq!(
+            store.profile(&dir.path().join("a")).as_deref(),
+            Some("refreshed-a")
+        );
+    }
+
+    #[test]
+    fn sync_backs_up_previous_credentials() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("refreshed-a"));
+        add_profile(dir.path(), &store, "a", "creds-a");
+
+        manager.sync_active_to_profile("a");
+
+        assert_eq!(
+            backup::read_version(&dir.path().join("a"), 1).unwrap(),
+            "creds-a"
+        );
+    }
+
+    #[test]
+    fn restore_rolls_back_profile_and_active_credentials() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("bad-a"));
+        add_profile(dir.path(), &store, "a", "creds-a");
+        fs::write(dir.path().join("_current"), "a\n").unwrap();
+        manager.sync_active_to_profile("a");
+
+        manager.restore("a", 1).unwrap();
+
+        assert_eq!(
+            store.profile(&dir.path().join("a")).as_deref(),
+            Some("creds-a")
+        );
+        assert_eq!(store.active().as_deref(), Some("creds-a"));
+        // The replaced credentials become the newest backup.
+        assert_eq!(
+            backup::read_version(&dir.path().join("a"), 1).unwrap(),
+            "bad-a"
+        );
+        assert!(manager.restore("a", 5).is_err());
+        assert!(manager.restore("missing", 1).is_err());
+    }
+
+    #[test]
+    fn sync_does_nothing_without_current_profile() {
+        let (dir, store, manager) = setup(MemoryStore::with_active("creds"));
+        add_profile(dir.path(), &store, "a", "creds-a");
+
+        manager.sync_active_to_current_profile();
+
+        assert_eq!(
+            store.profile(&dir.path().join("a")).as_deref(),
+            Some("creds-a")
+        );
+    }
+}
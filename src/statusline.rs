@@ -0,0 +1,96 @@
+use anyhow::Result;
+use chrono::Utc;
+
+use crate::config::Config;
+use crate::display::{capitalize_first, danger_color};
+use crate::tool::Tool;
+use crate::usage_cache::{StoredEntry, StoredUsage};
+
+fn short_label(label: &str) -> &str {
+    match label {
+        "5-hour" => "5h",
+        "Weekly" => "wk",
+        other => other,
+    }
+}
+
+fn format_statusline(profile: Option<&str>, entry: Option<&StoredEntry>) -> String {
+    let Some(profile) = profile else {
+        return "aip: no profile".to_string();
+    };
+    let mut parts = vec![match entry.and_then(|e| e.plan_type.as_deref()) {
+        Some(plan) => format!("{} ({})", profile, capitalize_first(plan)),
+        None => profile.to_string(),
+    }];
+    match entry {
+        Some(entry) => parts.extend(entry.windows.iter().map(|w| {
+            format!(
+                "{} {}{:.0}%\x1b[0m",
+                short_label(&w.label),
+                danger_color(w.percent),
+                w.percent
+            )
+        })),
+        None => parts.push("\x1b[2mno usage data\x1b[0m".to_string()),
+    }
+    parts.join(" · ")
+}
+
+/// Prints the active profile and its cached usage on one line.
+///
+/// Only local files are read, so it is cheap enough for Claude Code's
+/// `statusLine`; the session JSON it sends on stdin is not needed.
+pub fn cmd_statusline(tool_arg: Option<String>) -> Result<()> {
+    let tool = match tool_arg {
+        Some(t) => t.parse()?,
+        None => Tool::Claude,
+    };
+    let profile = tool.current_profile().ok().flatten();
+    let stored = StoredUsage::load();
+    let max_age = Config::load().usage_cache_max_age();
+    let entry = profile
+        .as_deref()
+        .and_then(|p| stored.fresh_entry(tool, p, max_age, Utc::now()));
+    println!("{}", format_statusline(profile.as_deref(), entry));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage_cache::StoredWindow;
+
+    #[test]
+    fn statusline_shows_plan_and_coloured_windows() {
+        let entry = StoredEntry {
+            fetched_at: chrono::Utc::now(),
+            plan_type: Some("max".to_string()),
+            windows: vec![
+                StoredWindow {
+                    label: "5-hour".to_string(),
+                    percent: 42.4,
+                    resets_at: None,
+                },
+                StoredWindow {
+                    label: "Weekly".to_string(),
+                    percent: 91.0,
+                    resets_at: None,
+                },
+            ],
+        };
+
+        assert_eq!(
+            format_statusline(Some("work"), Some(&entry)),
+            "work (Max) · 5h \x1b[32m42%\x1b[0m · wk \x1b[31m91%\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn statusline_without_cache_or_profile() {
+        assert_eq!(
+            format_statusline(Some("work"), None),
+            "work · \x1b[2mno usage data\x1b[0m"
+        );
+        assert_eq!(format_statusline(None, None), "aip: no profile");
+    }
+}
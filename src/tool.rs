@@ -1,10 +1,26 @@
 use std::collections::HashSet;
+use std::ffi::OsString;
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
 
 use anyhow::{Result, anyhow};
+use chrono::Utc;
 
-use crate::fs_util;
+use crate::config::Config;
+use crate::fs_util::{self, FileLock};
+use crate::trash;
+
+/// How long to wait for another aip process to release `profiles/.lock`.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The `claude_home` / `codex_home` override from aip's config, read once
+/// per process.
+fn home_override(tool: Tool) -> Option<&'static Path> {
+    static CONFIG: OnceLock<Config> = OnceLock::new();
+    CONFIG.get_or_init(Config::load).home_override(tool)
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Tool {
@@ -15,14 +31,49 @@ pub enum Tool {
 impl Tool {
     pub const ALL: [Tool; 2] = [Tool::Claude, Tool::Codex];
 
-    pub fn home_dir(&self) -> Result<PathBuf> {
-        let home = dirs::home_dir().ok_or_else(|| anyhow!("could not determine home directory"))?;
+    /// Short identifier accepted on the command line and used in machine-readable output.
+    pub fn id(&self) -> &'static str {
+        match self {
+            Tool::Claude => "claude",
+            Tool::Codex => "codex",
+        }
+    }
+
+    /// Environment variable the tool reads to locate its config directory.
+    pub fn config_dir_env(&self) -> &'static str {
+        match self {
+            Tool::Claude => "CLAUDE_CONFIG_DIR",
+            Tool::Codex => "CODEX_HOME",
+        }
+    }
+
+    /// File name of the credentials saved in each profile directory.
+    pub fn credential_file(&self) -> &'static str {
+        match self {
+            Tool::Claude => "credentials.json",
+            Tool::Codex => "auth.json",
+        }
+    }
+
+    fn default_home_name(&self) -> &'static str {
         match self {
-            Tool::Claude => Ok(home.join(".claude")),
-            Tool::Codex => Ok(home.join(".codex")),
+            Tool::Claude => ".claude",
+            Tool::Codex => ".codex",
         }
     }
 
+    /// The tool's config directory: its own environment variable wins, then
+    /// the override in aip's config, then the default under `$HOME`.
+    pub fn home_dir(&self) -> Result<PathBuf> {
+        let home = dirs::home_dir().ok_or_else(|| anyhow!("could not determine home directory"))?;
+        Ok(resolve_home_dir(
+            std::env::var_os(self.config_dir_env()),
+            home_override(*self),
+            &home,
+            self.default_home_name(),
+        ))
+    }
+
     pub fn profiles_dir(&self) -> Result<PathBuf> {
         Ok(self.home_dir()?.join("profiles"))
     }
@@ -32,39 +83,26 @@ impl Tool {
     }
 
     pub fn current_profile(&self) -> Result<Option<String>> {
-        let path = self.current_file()?;
-        if !path.exists() {
-            return Ok(None);
-        }
-        let content = std::fs::read_to_string(&path)?;
-        let trimmed = content.trim();
-        if trimmed.is_empty() {
-            return Ok(None);
-        }
-        if self.profile_dir(trimmed).is_err() {
-            return Ok(None);
-        }
-        Ok(Some(trimmed.to_string()))
+        read_current_marker(&self.current_file()?)
     }
 
     pub fn profile_dir(&self, name: &str) -> Result<PathBuf> {
-        if name.is_empty()
-            || !name
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
-        {
-            return Err(anyhow!(
-                "invalid profile name: '{}' (only ASCII alphanumeric, '-', '_' allowed)",
-                name
-            ));
-        }
-        if name == "_current" || name == "_order" {
-            return Err(anyhow!("'{}' is a reserved name", name));
-        }
+        validate_profile_name(name)?;
         Ok(self.profiles_dir()?.join(name))
     }
 
-    pub fn delete_profile(&self, name: &str) -> Result<()> {
+    /// Takes the advisory lock that serializes writes under `profiles/`
+    /// across aip processes. It is not reentrant.
+    pub fn lock_profiles(&self) -> Result<FileLock> {
+        let profiles_dir = self.profiles_dir()?;
+        std::fs::create_dir_all(&profiles_dir)?;
+        FileLock::acquire(&profiles_dir.join(".lock"), LOCK_TIMEOUT)
+    }
+
+    /// Moves a profile into `profiles/.trash` and returns its trash entry id.
+    /// Entries past the configured retention are purged along the way.
+    pub fn delete_profile(&self, name: &str) -> Result<String> {
+        let _lock = self.lock_profiles()?;
         let current = self.current_profile()?;
         if current.as_deref() == Some(name) {
             return Err(anyhow!("cannot delete the current profile '{}'", name));
@@ -75,7 +113,10 @@ impl Tool {
             return Err(anyhow!("profile '{}' does not exist for {}", name, self));
         }
 
-        std::fs::remove_dir_all(&profile_dir)?;
+        let profiles_dir = self.profiles_dir()?;
+        let now = Utc::now();
+        let id = trash::move_to_trash(&profiles_dir, name, now)?;
+        let _ = trash::purge_expired(&profiles_dir, Config::load().trash_retention(), now);
 
         // Clean up _order file to remove the deleted profile name.
         let order_file = self.order_file()?;
@@ -86,7 +127,7 @@ impl Tool {
             let _ = fs_util::atomic_write(&order_file, &(filtered.join("\n") + "\n"));
         }
 
-        Ok(())
+        Ok(id)
     }
 
     pub fn order_file(&self) -> Result<PathBuf> {
@@ -95,6 +136,7 @@ impl Tool {
 
     pub fn save_profile_order(&self, profiles: &[String]) -> Result<()> {
         let content = profiles.join("\n") + "\n";
+        let _lock = self.lock_profiles()?;
         fs_util::atomic_write(&self.order_file()?, &content)
     }
 
@@ -109,7 +151,7 @@ impl Tool {
             let Ok(name) = entry.file_name().into_string() else {
                 continue;
             };
-            if name == "_current" || name == "_order" {
+            if name.starts_with('.') || name == "_current" || name == "_order" {
                 continue;
             }
             if entry.file_type()?.is_dir() {
@@ -125,6 +167,58 @@ impl Tool {
     }
 }
 
+fn resolve_home_dir(
+    env: Option<OsString>,
+    config_override: Option<&Path>,
+    home: &Path,
+    default_name: &str,
+) -> PathBuf {
+    if let Some(dir) = env.filter(|v| !v.is_empty()) {
+        return expand_tilde(PathBuf::from(dir), home);
+    }
+    if let Some(dir) = config_override {
+        return expand_tilde(dir.to_path_buf(), home);
+    }
+    home.join(default_name)
+}
+
+fn expand_tilde(path: PathBuf, home: &Path) -> PathBuf {
+    match path.strip_prefix("~") {
+        Ok(rest) => home.join(rest),
+        Err(_) => path,
+    }
+}
+
+pub fn validate_profile_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "invalid profile name: '{}' (only ASCII alphanumeric, '-', '_' allowed)",
+            name
+        ));
+    }
+    if name == "_current" || name == "_order" {
+        return Err(anyhow!("'{}' is a reserved name", name));
+    }
+    Ok(())
+}
+
+/// Reads a `_current` marker file, ignoring missing, empty or invalid content.
+pub fn read_current_marker(path: &Path) -> Result<Option<String>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(path)?;
+    let trimmed = content.trim();
+    if trimmed.is_empty() || validate_profile_name(trimmed).is_err() {
+        return Ok(None);
+    }
+    Ok(Some(trimmed.to_string()))
+}
+
 fn merge_profiles_with_order(
     existing: HashSet<String>,
     order_content: Option<&str>,
@@ -176,6 +270,43 @@ impl std::str::FromStr for Tool {
 mod tests {
     use super::*;
 
+    #[test]
+    fn resolve_home_dir_prefers_env_then_config_then_default() {
+        let home = Path::new("/home/me");
+
+        assert_eq!(
+            resolve_home_dir(
+                Some(OsString::from("/work/.codex")),
+                Some(Path::new("/cfg/.codex")),
+                home,
+                ".codex"
+            ),
+            PathBuf::from("/work/.codex")
+        );
+        assert_eq!(
+            resolve_home_dir(None, Some(Path::new("/cfg/.codex")), home, ".codex"),
+            PathBuf::from("/cfg/.codex")
+        );
+        assert_eq!(
+            resolve_home_dir(Some(OsString::new()), None, home, ".codex"),
+            PathBuf::from("/home/me/.codex")
+        );
+    }
+
+    #[test]
+    fn resolve_home_dir_expands_tilde() {
+        let home = Path::new("/home/me");
+
+        assert_eq!(
+            resolve_home_dir(None, Some(Path::new("~/alt/claude")), home, ".claude"),
+            PathBuf::from("/home/me/alt/claude")
+        );
+        assert_eq!(
+            resolve_home_dir(Some(OsString::from("~")), None, home, ".claude"),
+            PathBuf::from("/home/me")
+        );
+    }
+
     #[test]
     fn profile_dir_rejects_path_traversal() {
         assert!(Tool::Claude.profile_dir("../evil").is_err());
@@ -204,6 +335,29 @@ mod tests {
         assert!(Tool::Claude.profile_dir("test_123").is_ok());
     }
 
+    #[test]
+    fn read_current_marker_trims_and_validates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("_current");
+        assert_eq!(read_current_marker(&path).unwrap(), None);
+
+        std::fs::write(&path, "work\n").unwrap();
+        assert_eq!(read_current_marker(&path).unwrap().as_deref(), Some("work"));
+
+        std::fs::write(&path, "../evil\n").unwrap();
+        assert_eq!(read_current_marker(&path).unwrap(), None);
+
+        std::fs::write(&path, "  \n").unwrap();
+        assert_eq!(read_current_marker(&path).unwrap(), None);
+    }
+
+    #[test]
+    fn id_round_trips_through_from_str() {
+        for tool in Tool::ALL {
+            assert_eq!(tool.id().parse::<Tool>().unwrap(), tool);
+        }
+    }
+
     #[test]
     fn merge_profiles_with_order_no_order_file() {
         let existing = HashSet::from(["c".to_string(), "a".to_string(), "b".to_string()]);
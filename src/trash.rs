@@ -0,0 +1,299 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Result, anyhow, bail};
+use chrono::{DateTime, Local, NaiveDateTime, TimeDelta, Utc};
+use dialoguer::Confirm;
+
+use crate::cli::TrashAction;
+use crate::config::Config;
+use crate::display;
+use crate::tool::{self, Tool};
+
+const TRASH_DIR: &str = ".trash";
+const STAMP_FORMAT: &str = "%Y%m%dT%H%M%S%3fZ";
+
+/// A deleted profile kept under `profiles/.trash/<name>-<timestamp>`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrashEntry {
+    /// Directory name inside the trash.
+    pub id: String,
+    pub profile: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+impl TrashEntry {
+    fn parse(id: &str) -> Option<Self> {
+        let (profile, stamp) = id.rsplit_once('-')?;
+        tool::validate_profile_name(profile).ok()?;
+        let deleted_at = NaiveDateTime::parse_from_str(stamp, STAMP_FORMAT)
+            .ok()?
+            .and_utc();
+        Some(Self {
+            id: id.to_string(),
+            profile: profile.to_string(),
+            deleted_at,
+        })
+    }
+}
+
+fn trash_dir(profiles_dir: &Path) -> PathBuf {
+    profiles_dir.join(TRASH_DIR)
+}
+
+/// Moves `profiles_dir/<name>` into the trash and returns the entry id.
+pub fn move_to_trash(profiles_dir: &Path, name: &str, now: DateTime<Utc>) -> Result<String> {
+    let trash = trash_dir(profiles_dir);
+    fs::create_dir_all(&trash)?;
+    let id = format!("{}-{}", name, now.format(STAMP_FORMAT));
+    fs::rename(profiles_dir.join(name), trash.join(&id))?;
+    Ok(id)
+}
+
+/// Entries in the trash, newest first.
+fn entries(profiles_dir: &Path) -> Result<Vec<TrashEntry>> {
+    let trash = trash_dir(profiles_dir);
+    if !trash.exists() {
+        return Ok(vec![]);
+    }
+    let mut entries: Vec<TrashEntry> = fs::read_dir(&trash)?
+        .filter_map(|entry| entry.ok()?.file_name().to_str().and_then(TrashEntry::parse))
+        .collect();
+    entries.sort_by(|a, b| b.deleted_at.cmp(&a.deleted_at).then(a.id.cmp(&b.id)));
+    Ok(entries)
+}
+
+/// Finds an entry by id, falling back to the newest entry of a profile name.
+fn find<'a>(entries: &'a [TrashEntry], query: &str) -> Option<&'a TrashEntry> {
+    entries
+        .iter()
+        .find(|e| e.id == query)
+        .or_else(|| entries.iter().find(|e| e.profile == query))
+}
+
+fn restore_from(profiles_dir: &Path, query: &str) -> Result<TrashEntry> {
+    let entries = entries(profiles_dir)?;
+    let entry = find(&entries, query)
+        .ok_or_else(|| anyhow!("no trashed profile matches '{}'", query))?
+        .clone();
+    if profiles_dir.join(&entry.profile).exists() {
+        bail!(
+            "profile '{}' already exists; delete it before restoring",
+            entry.profile
+        );
+    }
+    fs::rename(
+        trash_dir(profiles_dir).join(&entry.id),
+        profiles_dir.join(&entry.profile),
+    )?;
+    Ok(entry)
+}
+
+/// Permanently removes entries deleted before `cutoff`, or all of them.
+fn purge(profiles_dir: &Path, cutoff: Option<DateTime<Utc>>) -> Result<usize> {
+    let mut purged = 0;
+    for entry in entries(profiles_dir)? {
+        if cutoff.is_none_or(|cutoff| entry.deleted_at < cutoff) {
+            fs::remove_dir_all(trash_dir(profiles_dir).join(&entry.id))?;
+            purged += 1;
+        }
+    }
+    Ok(purged)
+}
+
+/// Purges entries older than `retention`; `None` keeps them forever.
+pub fn purge_expired(
+    profiles_dir: &Path,
+    retention: Option<Duration>,
+    now: DateTime<Utc>,
+) -> Result<usize> {
+    let Some(retention) = retention else {
+        return Ok(0);
+    };
+    purge(profiles_dir, Some(now - TimeDelta::from_std(retention)?))
+}
+
+/// Restores a trashed profile of `tool` by entry id or profile name.
+pub fn restore(tool: Tool, query: &str) -> Result<TrashEntry> {
+    let _lock = tool.lock_profiles()?;
+    restore_from(&tool.profiles_dir()?, query)
+}
+
+fn format_table(entries: &[(Tool, TrashEntry)]) -> Vec<String> {
+    let rows: Vec<Vec<String>> = entries
+        .iter()
+        .map(|(tool, entry)| {
+            vec![
+                tool.id().to_string(),
+                entry.profile.clone(),
+                entry
+                    .deleted_at
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M")
+                    .to_string(),
+                entry.id.clone(),
+            ]
+        })
+        .collect();
+    display::format_table(&["TOOL", "PROFILE", "DELETED", "ENTRY"], &rows)
+}
+
+fn selected_tools(tool_arg: Option<String>) -> Result<Vec<Tool>> {
+    Ok(match tool_arg {
+        Some(t) => vec![t.parse()?],
+        None => Tool::ALL.to_vec(),
+    })
+}
+
+pub fn cmd_trash(action: TrashAction) -> Result<()> {
+    match action {
+        TrashAction::List { tool } => {
+            let retention = Config::load().trash_retention();
+            let mut listed = Vec::new();
+            for tool in selected_tools(tool)? {
+                let profiles_dir = tool.profiles_dir()?;
+                if !trash_dir(&profiles_dir).exists() {
+                    continue;
+                }
+                let _lock = tool.lock_profiles()?;
+                purge_expired(&profiles_dir, retention, Utc::now())?;
+                listed.extend(entries(&profiles_dir)?.into_iter().map(|e| (tool, e)));
+            }
+            if listed.is_empty() {
+                println!("Trash is empty.");
+            } else {
+                for line in format_table(&listed) {
+                    println!("{}", line);
+                }
+            }
+        }
+        TrashAction::Restore { tool, entry } => {
+            let tool: Tool = tool.parse()?;
+            let restored = restore(tool, &entry)?;
+            println!("Restored profile '{}' for {}", restored.profile, tool);
+        }
+        TrashAction::Purge { tool, yes } => {
+            let tools = selected_tools(tool)?;
+            let mut count = 0;
+            for tool in &tools {
+                count += entries(&tool.profiles_dir()?)?.len();
+            }
+            if count == 0 {
+                println!("Trash is empty.");
+                return Ok(());
+            }
+            if !yes
+                && !Confirm::new()
+                    .with_prompt(format!("Permanently delete {} trashed profile(s)?", count))
+                    .default(false)
+                    .interact()?
+            {
+                return Ok(());
+            }
+            let mut purged = 0;
+            for tool in tools {
+                let _lock = tool.lock_profiles()?;
+                purged += purge(&tool.profiles_dir()?, None)?;
+            }
+            println!("Purged {} trashed profile(s)", purged);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    fn add_profile(profiles_dir: &Path, name: &str) {
+        let dir = profiles_dir.join(name);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("auth.json"), name).unwrap();
+    }
+
+    #[test]
+    fn move_to_trash_names_entry_after_profile_and_time() {
+        let dir = tempfile::tempdir().unwrap();
+        add_profile(dir.path(), "work-2");
+
+        let id = move_to_trash(dir.path(), "work-2", at("2026-01-02T03:04:05.678Z")).unwrap();
+
+        assert_eq!(id, "work-2-20260102T030405678Z");
+        assert!(!dir.path().join("work-2").exists());
+        assert_eq!(
+            entries(dir.path()).unwrap(),
+            vec![TrashEntry {
+                id,
+                profile: "work-2".to_string(),
+                deleted_at: at("2026-01-02T03:04:05.678Z"),
+            }]
+        );
+    }
+
+    #[test]
+    fn restore_picks_newest_entry_for_a_profile_name() {
+        let dir = tempfile::tempdir().unwrap();
+        add_profile(dir.path(), "work");
+        move_to_trash(dir.path(), "work", at("2026-01-01T00:00:00Z")).unwrap();
+        add_profile(dir.path(), "work");
+        fs::write(dir.path().join("work").join("auth.json"), "newer").unwrap();
+        move_to_trash(dir.path(), "work", at("2026-01-02T00:00:00Z")).unwrap();
+
+        let restored = restore_from(dir.path(), "work").unwrap();
+
+        assert_eq!(restored.deleted_at, at("2026-01-02T00:00:00Z"));
+        assert_eq!(
+            fs::read_to_string(dir.path().join("work").join("auth.json")).unwrap(),
+            "newer"
+        );
+        assert_eq!(entries(dir.path()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn restore_refuses_to_overwrite_existing_profile() {
+        let dir = tempfile::tempdir().unwrap();
+        add_profile(dir.path(), "work");
+        let id = move_to_trash(dir.path(), "work", at("2026-01-01T00:00:00Z")).unwrap();
+        add_profile(dir.path(), "work");
+
+        let err = restore_from(dir.path(), &id).unwrap_err();
+
+        assert!(err.to_string().contains("already exists"));
+        assert_eq!(entries(dir.path()).unwrap().len(), 1);
+        assert!(restore_from(dir.path(), "missing").is_err());
+    }
+
+    #[test]
+    fn purge_expired_keeps_entries_within_retention() {
+        let dir = tempfile::tempdir().unwrap();
+        add_profile(dir.path(), "old");
+        add_profile(dir.path(), "new");
+        move_to_trash(dir.path(), "old", at("2026-01-01T00:00:00Z")).unwrap();
+        move_to_trash(dir.path(), "new", at("2026-01-09T00:00:00Z")).unwrap();
+        let now = at("2026-01-10T00:00:00Z");
+        let week = Duration::from_secs(7 * 86400);
+
+        assert_eq!(purge_expired(dir.path(), None, now).unwrap(), 0);
+        assert_eq!(purge_expired(dir.path(), Some(week), now).unwrap(), 1);
+
+        let remaining = entries(dir.path()).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].profile, "new");
+        assert_eq!(purge(dir.path(), None).unwrap(), 1);
+        assert!(entries(dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn entries_ignore_unrecognized_names() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(trash_dir(dir.path()).join("notes")).unwrap();
+        fs::create_dir_all(trash_dir(dir.path()).join("work-yesterday")).unwrap();
+
+        assert!(entries(dir.path()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,1003 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::claude;
+use crate::claude::usage::RateLimitError;
+use crate::cli::UsageFormat;
+use crate::codex;
+use crate::codex::usage::RateLimits;
+use crate::config::Config;
+use crate::display::{self, format_reset_time};
+use crate::history;
+use crate::tool::Tool;
+use crate::usage_cache::{self, StoredUsage};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum UsageLine {
+    Data {
+        label: String,
+        percent: f64,
+        resets_at: Option<DateTime<Utc>>,
+    },
+    Text(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProfileUsageCache {
+    pub usage_lines: Vec<UsageLine>,
+    pub plan_type: Option<String>,
+    pub is_stale: bool,
+    /// End of a rate-limit retry-after window reported by the last fetch.
+    pub rate_limited_until: Option<DateTime<Utc>>,
+}
+pub type UsageCache = HashMap<String, ProfileUsageCache>;
+
+// --- Usage fetching ---
+
+fn format_retry_after(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 60 {
+        format!("Rate limited (resets in {}m {}s)", secs / 60, secs % 60)
+    } else if secs > 0 {
+        format!("Rate limited (resets in {}s)", secs)
+    } else {
+        "Rate limited".to_string()
+    }
+}
+
+/// Appends a fetch to the history and the on-disk cache. Both do blocking
+/// file I/O and the cache waits on its lock, so they run off the async workers.
+async fn persist(tool: Tool, cache: &UsageCache) {
+    let cache = cache.clone();
+    let _ = tokio::task::spawn_blocking(move || {
+        let _ = history::record(tool, &cache);
+        let _ = usage_cache::record(tool, &cache);
+    })
+    .await;
+}
+
+pub async fn prefetch_claude_usage(profiles: &[String]) -> UsageCache {
+    let results = claude::usage::fetch_profiles_usage(profiles.to_vec()).await;
+
+    let cache = results
+        .into_iter()
+        .map(|(profile, result)| {
+            let entry = match result {
+                Ok((usage, info)) => ProfileUsageCache {
+                    usage_lines: vec![
+                        UsageLine::Data {
+                            label: "5-hour".to_string(),
+                            percent: usage.five_hour.utilization,
+                            resets_at: usage.five_hour.resets_at,
+                        },
+                        UsageLine::Data {
+                            label: "Weekly".to_string(),
+                            percent: usage.seven_day.utilization,
+                            resets_at: usage.seven_day.resets_at,
+                        },
+                    ],
+                    plan_type: info.plan_type,
+                    is_stale: false,
+                    rate_limited_until: None,
+                },
+                Err(e) => {
+                    if let Some(rate_err) = e.downcast_ref::<RateLimitError>() {
+                        let retry = rate_err.retry_after;
+                        ProfileUsageCache {
+                            usage_lines: vec![UsageLine::Text(format_retry_after(retry))],
+                            plan_type: None,
+                            // retry-after:0 may indicate unsupported plan;
+                            // use stale cache to preserve previous usage data.
+                            is_stale: retry.is_zero(),
+                            rate_limited_until: (!retry.is_zero()).then(|| Utc::now() + retry),
+                        }
+                    } else {
+                        ProfileUsageCache {
+                            usage_lines: vec![UsageLine::Text(format!("Error: {}", e))],
+                            plan_type: None,
+                            is_stale: true,
+                            rate_limited_until: None,
+                        }
+                    }
+                }
+            };
+            (profile, entry)
+        })
+        .collect();
+    persist(Tool::Claude, &cache).await;
+    cache
+}
+
+/// Latest end of any rate-limit window in `caches`, if one is still active.
+pub fn rate_limited_until<'a>(
+    caches: impl IntoIterator<Item = &'a UsageCache>,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    caches
+        .into_iter()
+        .flat_map(|cache| cache.values())
+        .filter_map(|entry| entry.rate_limited_until)
+        .filter(|until| *until > now)
+        .max()
+}
+
+/// Merge new usage cache with old cache.
+///
+/// When a new entry is stale and old entry has valid data, keep the old data
+/// but mark it as stale so the UI can show "(stale)". Old entries count as
+/// valid when they are fresh or hold usage windows, such as those loaded from
+/// disk at startup.
+pub fn merge_usage_cache(new_cache: UsageCache, old_cache: Option<&UsageCache>) -> UsageCache {
+    let old = match old_cache {
+        Some(c) => c,
+        None => return new_cache,
+    };
+    let has_data = |entry: &ProfileUsageCache| {
+        entry
+            .usage_lines
+            .iter()
+            .any(|line| matches!(line, UsageLine::Data { .. }))
+    };
+    new_cache
+        .into_iter()
+        .map(|(profile, new_entry)| {
+            if new_entry.is_stale
+                && let Some(old_entry) = old.get(&profile)
+                && (!old_entry.is_stale || has_data(old_entry))
+            {
+                return (
+                    profile,
+                    ProfileUsageCache {
+                        is_stale: true,
+                        rate_limited_until: new_entry.rate_limited_until,
+                        ..old_entry.clone()
+                    },
+                );
+            }
+            (profile, new_entry)
+        })
+        .collect()
+}
+
+fn codex_usage_result(result: Result<Option<RateLimits>>) -> (Vec<UsageLine>, bool) {
+    match result {
+        Ok(Some(limits)) => {
+            let mut lines = Vec::new();
+            if let Some(primary) = &limits.primary {
+                lines.push(UsageLine::Data {
+                    label: "5-hour".to_string(),
+                    percent: primary.used_percent,
+                    resets_at: primary.resets_at_utc(),
+                });
+            }
+            if let Some(secondary) = &limits.secondary {
+                lines.push(UsageLine::Data {
+                    label: "Weekly".to_string(),
+                    percent: secondary.used_percent,
+                    resets_at: secondary.resets_at_utc(),
+                });
+            }
+            if lines.is_empty() {
+                (
+                    vec![UsageLine::Text("No usage data available".to_string())],
+                    false,
+                )
+            } else {
+                (lines, false)
+            }
+        }
+        Ok(None) => (
+            vec![UsageLine::Text("No usage data available".to_string())],
+            false,
+        ),
+        Err(e) => (vec![UsageLine::Text(format!("Error: {}", e))], true),
+    }
+}
+
+pub async fn prefetch_codex_usage(profiles: &[String]) -> UsageCache {
+    // Sync active auth.json to current profile before fetching usage,
+    // analogous to sync_keychain_to_current_profile for Claude.
+    let _ = tokio::task::spawn_blocking(codex::profile::sync_auth_to_current_profile).await;
+
+    let current = Tool::Codex.current_profile().ok().flatten();
+
+    let mut handles = Vec::new();
+    for p in profiles {
+        let p = p.clone();
+        let is_current = current.as_deref() == Some(p.as_str());
+        handles.push(tokio::spawn(async move {
+            let result = if is_current {
+                codex::usage::fetch_usage().await
+            } else {
+                async {
+                    let dir = Tool::Codex.profile_dir(&p)?;
+                    let auth_path = dir.join("auth.json");
+                    codex::usage::fetch_usage_from_auth(&auth_path).await
+                }
+                .await
+            };
+            // Read after fetching, since a token refresh rewrites the id_token.
+            let auth_dir = if is_current {
+                Tool::Codex.home_dir()
+            } else {
+                Tool::Codex.profile_dir(&p)
+            };
+            let plan_type = auth_dir
+                .ok()
+                .and_then(|dir| codex::usage::read_id_token_claims(&dir.join("auth.json")).ok())
+                .and_then(|claims| claims.plan_type);
+            let (usage_lines, is_stale) = codex_usage_result(result);
+            (
+                p,
+                ProfileUsageCache {
+                    usage_lines,
+                    plan_type,
+                    is_stale,
+                    rate_limited_until: None,
+                },
+            )
+        }));
+    }
+
+    let mut results = HashMap::new();
+    for handle in handles {
+        match handle.await {
+            Ok((p, entry)) => {
+                results.insert(p, entry);
+            }
+            Err(join_err) => {
+                eprintln!("prefetch_codex_usage task panicked: {}", join_err);
+            }
+        }
+    }
+    persist(Tool::Codex, &results).await;
+    results
+}
+
+// --- One-shot usage command ---
+
+/// Returned when a usage window is above the `--fail-above` threshold.
+#[derive(Debug)]
+pub struct UsageAboveThreshold {
+    tool: Tool,
+    profile: String,
+    label: String,
+    percent: f64,
+    threshold: f64,
+}
+
+impl UsageAboveThreshold {
+    pub fn exit_code(&self) -> u8 {
+        6
+    }
+}
+
+impl std::fmt::Display for UsageAboveThreshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} usage for {} profile '{}' is {:.1}% (above {:.1}%)",
+            self.label, self.tool, self.profile, self.percent, self.threshold
+        )
+    }
+}
+
+impl std::error::Error for UsageAboveThreshold {}
+
+/// Returned by `--fail-above` when a profile has no usage data to check.
+#[derive(Debug)]
+pub struct UsageUnavailable {
+    tool: Tool,
+    profile: String,
+    message: Option<String>,
+}
+
+impl UsageUnavailable {
+    pub fn exit_code(&self) -> u8 {
+        8
+    }
+}
+
+impl std::fmt::Display for UsageUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "no usage data for {} profile '{}'",
+            self.tool, self.profile
+        )?;
+        if let Some(message) = &self.message {
+            write!(f, " ({})", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for UsageUnavailable {}
+
+/// One profile's usage as printed by `aip usage` and emitted by `aip watch`.
+#[derive(Debug, Serialize)]
+pub struct UsageReport {
+    #[serde(skip)]
+    pub tool: Tool,
+    #[serde(rename = "tool")]
+    pub tool_id: &'static str,
+    pub profile: String,
+    pub current: bool,
+    pub plan_type: Option<String>,
+    pub stale: bool,
+    /// When the data was fetched, for reports read from the on-disk cache.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<DateTime<Utc>>,
+    pub windows: Vec<WindowReport>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WindowReport {
+    pub label: String,
+    pub used_percent: f64,
+    pub resets_at: Option<DateTime<Utc>>,
+}
+
+pub fn build_reports(
+    tool: Tool,
+    profiles: &[String],
+    current: Option<&str>,
+    cache: &UsageCache,
+) -> Vec<UsageReport> {
+    profiles
+        .iter()
+        .map(|profile| {
+            let entry = cache.get(profile);
+            let mut windows = Vec::new();
+            let mut messages = Vec::new();
+            for line in entry.map(|e| e.usage_lines.as_slice()).unwrap_or_default() {
+                match line {
+                    UsageLine::Data {
+                        label,
+                        percent,
+                        resets_at,
+                    } => windows.push(WindowReport {
+                        label: label.clone(),
+                        used_percent: *percent,
+                        resets_at: *resets_at,
+                    }),
+                    UsageLine::Text(text) => messages.push(text.clone()),
+                }
+            }
+            if entry.is_none() {
+                messages.push("No data".to_string());
+            }
+            UsageReport {
+                tool,
+                tool_id: tool.id(),
+                profile: profile.clone(),
+                current: current == Some(profile.as_str()),
+                plan_type: entry.and_then(|e| e.plan_type.clone()),
+                stale: entry.is_some_and(|e| e.is_stale),
+                fetched_at: None,
+                windows,
+                message: (!messages.is_empty()).then(|| messages.join("; ")),
+            }
+        })
+        .collect()
+}
+
+fn format_reset(resets_at: Option<DateTime<Utc>>) -> String {
+    match resets_at {
+        Some(reset_at) => format_reset_time(reset_at),
+        None => "not started".to_string(),
+    }
+}
+
+fn format_table(reports: &[UsageReport]) -> Vec<String> {
+    let mut rows = Vec::new();
+    for report in reports {
+        let stale = if report.stale { " (stale)" } else { "" };
+        for window in &report.windows {
+            rows.push(vec![
+                report.tool_id.to_string(),
+                report.profile.clone(),
+                window.label.clone(),
+                format!("{:.1}%{}", window.used_percent, stale),
+                format_reset(window.resets_at),
+            ]);
+        }
+        if let Some(message) = &report.message {
+            rows.push(vec![
+                report.tool_id.to_string(),
+                report.profile.clone(),
+                "-".to_string(),
+                "-".to_string(),
+                message.clone(),
+            ]);
+        }
+    }
+    display::format_table(&["TOOL", "PROFILE", "WINDOW", "USED", "RESETS"], &rows)
+}
+
+pub fn format_oneline(report: &UsageReport) -> String {
+    let mut parts: Vec<String> = report
+        .windows
+        .iter()
+        .map(|w| {
+            format!(
+                "{} {:.0}% (resets {})",
+                w.label,
+                w.used_percent,
+                format_reset(w.resets_at)
+            )
+        })
+        .collect();
+    parts.extend(report.message.clone());
+    let stale = if report.stale { " (stale)" } else { "" };
+    format!(
+        "{}/{}: {}{}",
+        report.tool_id,
+        report.profile,
+        parts.join(", "),
+        stale
+    )
+}
+
+fn check_threshold(reports: &[UsageReport], threshold: f64) -> Result<()> {
+    for report in reports {
+        for window in &report.windows {
+            if window.used_percent > threshold {
+                return Err(UsageAboveThreshold {
+                    tool: report.tool,
+                    profile: report.profile.clone(),
+                    label: window.label.clone(),
+                    percent: window.used_percent,
+                    threshold,
+                }
+                .into());
+            }
+        }
+    }
+    // A profile without data can't be shown to be under the threshold.
+    if let Some(report) = reports.iter().find(|r| r.windows.is_empty()) {
+        return Err(UsageUnavailable {
+            tool: report.tool,
+            profile: report.profile.clone(),
+            message: report.message.clone(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+pub async fn cmd_usage(
+    tool_arg: Option<String>,
+    profile_arg: Option<String>,
+    format: UsageFormat,
+    fail_above: Option<f64>,
+    cached: bool,
+) -> Result<()> {
+    let tools = match tool_arg {
+        Some(t) => vec![t.parse()?],
+        None => Tool::ALL.to_vec(),
+    };
+    let stored = cached.then(StoredUsage::load);
+    let max_age = Config::load().usage_cache_max_age();
+    let now = Utc::now();
+
+    let mut reports = Vec::new();
+    for tool in tools {
+        let mut profiles = tool.list_profiles()?;
+        if let Some(profile) = &profile_arg {
+            if !profiles.contains(profile) {
+                bail!("profile '{}' does not exist for {}", profile, tool);
+            }
+            profiles = vec![profile.clone()];
+        }
+        if profiles.is_empty() {
+            continue;
+        }
+
+        let cache = match (&stored, tool) {
+            (Some(stored), _) => stored.usage_cache(tool, max_age, now, false),
+            (None, Tool::Claude) => prefetch_claude_usage(&profiles).await,
+            (None, Tool::Codex) => prefetch_codex_usage(&profiles).await,
+        };
+        let current = tool.current_profile().ok().flatten();
+        let mut tool_reports = build_reports(tool, &profiles, current.as_deref(), &cache);
+        if let Some(stored) = &stored {
+            for report in &mut tool_reports {
+                report.fetched_at = stored
+                    .entry(tool, &report.profile)
+                    .map(|entry| entry.fetched_at);
+            }
+        }
+        reports.extend(tool_reports);
+    }
+
+    match format {
+        UsageFormat::Json => {
+            let output = serde_json::json!({ "profiles": reports });
+            println!("{}", serde_json::to_string_pretty(&output)?);
+        }
+        UsageFormat::Table => {
+            for line in format_table(&reports) {
+                println!("{}", line);
+            }
+        }
+        UsageFormat::Oneline => {
+            for report in &reports {
+                println!("{}", format_oneline(report));
+            }
+        }
+    }
+
+    match fail_above {
+        Some(threshold) => check_threshold(&reports, threshold),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codex::usage::RateWindow;
+
+    #[test]
+    fn merge_usage_cache_keeps_old_data_when_new_is_stale() {
+        let old: UsageCache = HashMap::from([(
+            "main".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Text("5-hour  40.0% used".to_string())],
+                plan_type: Some("pro".to_string()),
+                is_stale: false,
+                rate_limited_until: None,
+            },
+        )]);
+        let new: UsageCache = HashMap::from([(
+            "main".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Text("Rate limited".to_string())],
+                plan_type: None,
+                is_stale: true,
+                rate_limited_until: None,
+            },
+        )]);
+
+        let merged = merge_usage_cache(new, Some(&old));
+        let entry = &merged["main"];
+        assert!(entry.is_stale);
+        assert_eq!(
+            entry.usage_lines,
+            vec![UsageLine::Text("5-hour  40.0% used".to_string())]
+        );
+        assert_eq!(entry.plan_type, Some("pro".to_string()));
+    }
+
+    #[test]
+    fn merge_usage_cache_uses_new_data_when_not_stale() {
+        let old: UsageCache = HashMap::from([(
+            "main".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Text("5-hour  40.0% used".to_string())],
+                plan_type: Some("pro".to_string()),
+                is_stale: false,
+                rate_limited_until: None,
+            },
+        )]);
+        let new: UsageCache = HashMap::from([(
+            "main".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Text("5-hour  50.0% used".to_string())],
+                plan_type: Some("pro".to_string()),
+                is_stale: false,
+                rate_limited_until: None,
+            },
+        )]);
+
+        let merged = merge_usage_cache(new, Some(&old));
+        let entry = &merged["main"];
+        assert!(!entry.is_stale);
+        assert_eq!(
+            entry.usage_lines,
+            vec![UsageLine::Text("5-hour  50.0% used".to_string())]
+        );
+    }
+
+    #[test]
+    fn merge_usage_cache_uses_fallback_when_no_old_data() {
+        let new: UsageCache = HashMap::from([(
+            "main".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Text("Rate limited".to_string())],
+                plan_type: None,
+                is_stale: true,
+                rate_limited_until: None,
+            },
+        )]);
+
+        let merged = merge_usage_cache(new, None);
+        let entry = &merged["main"];
+        assert!(entry.is_stale);
+        assert_eq!(
+            entry.usage_lines,
+            vec![UsageLine::Text("Rate limited".to_string())]
+        );
+    }
+
+    #[test]
+    fn merge_usage_cache_uses_new_data_when_old_also_stale() {
+        let old: UsageCache = HashMap::from([(
+            "main".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Text("5-hour  40.0% used".to_string())],
+                plan_type: Some("pro".to_string()),
+                is_stale: true,
+                rate_limited_until: None,
+            },
+        )]);
+        let new: UsageCache = HashMap::from([(
+            "main".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Text("Rate limited".to_string())],
+                plan_type: None,
+                is_stale: true,
+                rate_limited_until: None,
+            },
+        )]);
+
+        let merged = merge_usage_cache(new, Some(&old));
+        let entry = &merged["main"];
+        assert!(entry.is_stale);
+        // Both old and new are stale, so new data is used (no fallback to stale old)
+        assert_eq!(
+            entry.usage_lines,
+            vec![UsageLine::Text("Rate limited".to_string())]
+        );
+    }
+
+    #[test]
+    fn merge_usage_cache_keeps_stale_data_loaded_from_disk() {
+        let old: UsageCache = HashMap::from([(
+            "main".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Data {
+                    label: "5-hour".to_string(),
+                    percent: 40.0,
+                    resets_at: None,
+                }],
+                plan_type: Some("pro".to_string()),
+                is_stale: true,
+                rate_limited_until: None,
+            },
+        )]);
+        let new: UsageCache = HashMap::from([(
+            "main".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Text("Error: timed out".to_string())],
+                plan_type: None,
+                is_stale: true,
+                rate_limited_until: None,
+            },
+        )]);
+
+        let merged = merge_usage_cache(new, Some(&old));
+        let entry = &merged["main"];
+        assert!(entry.is_stale);
+        assert_eq!(entry.usage_lines, old["main"].usage_lines);
+    }
+
+    #[test]
+    fn merge_usage_cache_preserves_codex_data_on_error() {
+        let old: UsageCache = HashMap::from([(
+            "default".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![
+                    UsageLine::Text("5-hour  50.0% left".to_string()),
+                    UsageLine::Text("Weekly  70.0% left".to_string()),
+                ],
+                plan_type: None,
+                is_stale: false,
+                rate_limited_until: None,
+            },
+        )]);
+        let new: UsageCache = HashMap::from([(
+            "default".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Text("Error: connection refused".to_string())],
+                plan_type: None,
+                is_stale: true,
+                rate_limited_until: None,
+            },
+        )]);
+
+        let merged = merge_usage_cache(new, Some(&old));
+        let entry = &merged["default"];
+        assert!(entry.is_stale);
+        // Old valid data is preserved instead of being replaced with error
+        assert_eq!(
+            entry.usage_lines,
+            vec![
+                UsageLine::Text("5-hour  50.0% left".to_string()),
+                UsageLine::Text("Weekly  70.0% left".to_string()),
+            ]
+        );
+    }
+
+    // --- format_retry_after tests ---
+
+    #[test]
+    fn format_retry_after_minutes_and_seconds() {
+        let result = format_retry_after(Duration::from_secs(125));
+        assert_eq!(result, "Rate limited (resets in 2m 5s)");
+    }
+
+    #[test]
+    fn format_retry_after_seconds_only() {
+        let result = format_retry_after(Duration::from_secs(30));
+        assert_eq!(result, "Rate limited (resets in 30s)");
+    }
+
+    #[test]
+    fn format_retry_after_zero() {
+        let result = format_retry_after(Duration::from_secs(0));
+        assert_eq!(result, "Rate limited");
+    }
+
+    #[test]
+    fn format_retry_after_boundary_60s() {
+        let result = format_retry_after(Duration::from_secs(60));
+        assert_eq!(result, "Rate limited (resets in 1m 0s)");
+    }
+
+    // --- codex_usage_result tests ---
+
+    #[test]
+    fn codex_usage_result_with_both_windows() {
+        let limits = RateLimits {
+            primary: Some(RateWindow {
+                used_percent: 50.0,
+                resets_at: Some(1700000000),
+            }),
+            secondary: Some(RateWindow {
+                used_percent: 30.0,
+                resets_at: Some(1700100000),
+            }),
+        };
+        let (lines, is_stale) = codex_usage_result(Ok(Some(limits)));
+        assert_eq!(lines.len(), 2);
+        assert!(matches!(&lines[0], UsageLine::Data { label, .. } if label == "5-hour"));
+        assert!(matches!(&lines[1], UsageLine::Data { label, .. } if label == "Weekly"));
+        assert!(!is_stale);
+    }
+
+    #[test]
+    fn codex_usage_result_empty_windows_returns_no_data() {
+        let limits = RateLimits {
+            primary: None,
+            secondary: None,
+        };
+        let (lines, is_stale) = codex_usage_result(Ok(Some(limits)));
+        assert_eq!(
+            lines,
+            vec![UsageLine::Text("No usage data available".to_string())]
+        );
+        assert!(!is_stale);
+    }
+
+    #[test]
+    fn codex_usage_result_none_returns_no_data() {
+        let (lines, is_stale) = codex_usage_result(Ok(None));
+        assert_eq!(
+            lines,
+            vec![UsageLine::Text("No usage data available".to_string())]
+        );
+        assert!(!is_stale);
+    }
+
+    #[test]
+    fn codex_usage_result_error_is_stale() {
+        let (lines, is_stale) = codex_usage_result(Err(anyhow::anyhow!("connection failed")));
+        assert_eq!(lines.len(), 1);
+        assert!(
+            matches!(&lines[0], UsageLine::Text(t) if t.starts_with("Error: ") && t.contains("connection failed"))
+        );
+        assert!(is_stale);
+    }
+
+    #[test]
+    fn rate_limited_until_returns_latest_active_window() {
+        let now = Utc::now();
+        let entry = |until: Option<DateTime<Utc>>| ProfileUsageCache {
+            usage_lines: vec![],
+            plan_type: None,
+            is_stale: false,
+            rate_limited_until: until,
+        };
+        let cache: UsageCache = HashMap::from([
+            (
+                "a".to_string(),
+                entry(Some(now + chrono::Duration::seconds(30))),
+            ),
+            (
+                "b".to_string(),
+                entry(Some(now + chrono::Duration::seconds(90))),
+            ),
+            (
+                "c".to_string(),
+                entry(Some(now - chrono::Duration::seconds(10))),
+            ),
+            ("d".to_string(), entry(None)),
+        ]);
+
+        assert_eq!(
+            rate_limited_until([&cache], now),
+            Some(now + chrono::Duration::seconds(90))
+        );
+        assert_eq!(
+            rate_limited_until([&cache], now + chrono::Duration::seconds(100)),
+            None
+        );
+    }
+
+    #[test]
+    fn merge_usage_cache_keeps_rate_limit_of_new_entry() {
+        let until = Utc::now() + chrono::Duration::seconds(60);
+        let old: UsageCache = HashMap::from([(
+            "main".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Text("5-hour  40.0% used".to_string())],
+                plan_type: None,
+                is_stale: false,
+                rate_limited_until: None,
+            },
+        )]);
+        let new: UsageCache = HashMap::from([(
+            "main".to_string(),
+            ProfileUsageCache {
+                usage_lines: vec![UsageLine::Text("Rate limited".to_string())],
+                plan_type: None,
+                is_stale: true,
+                rate_limited_until: Some(until),
+            },
+        )]);
+
+        let merged = merge_usage_cache(new, Some(&old));
+
+        assert_eq!(merged["main"].rate_limited_until, Some(until));
+    }
+
+    // --- one-shot usage tests ---
+
+    fn sample_cache() -> UsageCache {
+        HashMap::from([
+            (
+                "work".to_string(),
+                ProfileUsageCache {
+                    usage_lines: vec![
+                        UsageLine::Data {
+                            label: "5-hour".to_string(),
+                            percent: 42.0,
+                            resets_at: None,
+                        },
+                        UsageLine::Data {
+                            label: "Weekly".to_string(),
+                            percent: 85.5,
+                            resets_at: DateTime::from_timestamp(1700000000, 0),
+                        },
+                    ],
+                    plan_type: Some("max".to_string()),
+                    is_stale: false,
+                    rate_limited_until: None,
+                },
+            ),
+            (
+                "home".to_string(),
+                ProfileUsageCache {
+                    usage_lines: vec![UsageLine::Text("Rate limited".to_string())],
+                    plan_type: None,
+                    is_stale: true,
+                    rate_limited_until: None,
+                },
+            ),
+        ])
+    }
+
+    fn sample_reports() -> Vec<UsageReport> {
+        build_reports(
+            Tool::Claude,
+            &["work".to_string(), "home".to_string(), "new".to_string()],
+            Some("work"),
+            &sample_cache(),
+        )
+    }
+
+    #[test]
+    fn build_reports_keeps_profile_order_and_splits_messages() {
+        let reports = sample_reports();
+
+        assert_eq!(reports.len(), 3);
+        assert_eq!(reports[0].profile, "work");
+        assert!(reports[0].current);
+        assert_eq!(reports[0].windows.len(), 2);
+        assert_eq!(reports[0].message, None);
+        assert_eq!(reports[1].message.as_deref(), Some("Rate limited"));
+        assert!(reports[1].stale);
+        assert_eq!(reports[2].message.as_deref(), Some("No data"));
+    }
+
+    #[test]
+    fn json_report_has_stable_shape() {
+        let reports = sample_reports();
+        let value = serde_json::to_value(&reports[0]).unwrap();
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "tool": "claude",
+                "profile": "work",
+                "current": true,
+                "plan_type": "max",
+                "stale": false,
+                "windows": [
+                    { "label": "5-hour", "used_percent": 42.0, "resets_at": null },
+                    {
+                        "label": "Weekly",
+                        "used_percent": 85.5,
+                        "resets_at": "2023-11-14T22:13:20Z"
+                    }
+                ],
+                "message": null
+            })
+        );
+    }
+
+    #[test]
+    fn format_table_lists_windows_and_messages() {
+        let lines = format_table(&sample_reports());
+
+        assert_eq!(lines[0], "TOOL    PROFILE  WINDOW  USED   RESETS");
+        assert_eq!(lines[1], "claude  work     5-hour  42.0%  not started");
+        assert!(lines[2].starts_with("claude  work     Weekly  85.5%  "));
+        assert_eq!(lines[3], "claude  home     -       -      Rate limited");
+        assert_eq!(lines[4], "claude  new      -       -      No data");
+    }
+
+    #[test]
+    fn format_oneline_joins_windows() {
+        let reports = sample_reports();
+
+        assert!(
+            format_oneline(&reports[0])
+                .starts_with("claude/work: 5-hour 42% (resets not started), Weekly 86% (resets ")
+        );
+        assert_eq!(
+            format_oneline(&reports[1]),
+            "claude/home: Rate limited (stale)"
+        );
+    }
+
+    #[test]
+    fn check_threshold_fails_when_any_window_is_above() {
+        let reports = sample_reports();
+
+        let err = check_threshold(&reports, 80.0).unwrap_err();
+        let above = err.downcast_ref::<UsageAboveThreshold>().unwrap();
+        assert_eq!(above.label, "Weekly");
+        assert_eq!(above.exit_code(), 6);
+
+        assert!(check_threshold(&reports[..1], 85.5).is_ok());
+    }
+
+    #[test]
+    fn check_threshold_fails_when_a_profile_has_no_data() {
+        let reports = sample_reports();
+
+        let err = check_threshold(&reports, 85.5).unwrap_err();
+        let unavailable = err.downcast_ref::<UsageUnavailable>().unwrap();
+        assert_eq!(unavailable.profile, "home");
+        assert_eq!(unavailable.exit_code(), 8);
+        assert!(err.to_string().contains("Rate limited"));
+    }
+}
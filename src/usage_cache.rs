@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::fs_util::{FileLock, atomic_write};
+use crate::tool::Tool;
+use crate::usage::{ProfileUsageCache, UsageCache, UsageLine};
+
+/// The last successfully fetched usage of every profile, kept in
+/// `usage-cache.json` so readers never have to hit the network.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StoredUsage {
+    /// Entries by tool id, then profile name.
+    #[serde(default)]
+    pub tools: BTreeMap<String, BTreeMap<String, StoredEntry>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredEntry {
+    pub fetched_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub plan_type: Option<String>,
+    pub windows: Vec<StoredWindow>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredWindow {
+    pub label: String,
+    pub percent: f64,
+    pub resets_at: Option<DateTime<Utc>>,
+}
+
+/// How long to wait for another process writing the cache.
+const LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn is_within(fetched_at: DateTime<Utc>, max_age: Duration, now: DateTime<Utc>) -> bool {
+    (now - fetched_at)
+        .to_std()
+        .map_or(true, |age| age <= max_age)
+}
+
+fn cache_path() -> Result<PathBuf> {
+    Ok(config::aip_dir()?.join("usage-cache.json"))
+}
+
+impl StoredUsage {
+    fn load_from(path: &Path) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    fn save_to(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        atomic_write(path, &serde_json::to_string_pretty(self)?)
+    }
+
+    /// Loads the cache, treating a missing or unreadable file as empty.
+    pub fn load() -> Self {
+        cache_path()
+            .map(|path| Self::load_from(&path))
+            .unwrap_or_default()
+    }
+
+    pub fn entry(&self, tool: Tool, profile: &str) -> Option<&StoredEntry> {
+        self.tools.get(tool.id())?.get(profile)
+    }
+
+    /// Like [`entry`](Self::entry), ignoring entries fetched more than `max_age` ago.
+    pub fn fresh_entry(
+        &self,
+        tool: Tool,
+        profile: &str,
+        max_age: Duration,
+        now: DateTime<Utc>,
+    ) -> Option<&StoredEntry> {
+        self.entry(tool, profile)
+            .filter(|e| is_within(e.fetched_at, max_age, now))
+    }
+
+    /// The entries of `tool` younger than `max_age` as an in-memory cache.
+    pub fn usage_cache(
+        &self,
+        tool: Tool,
+        max_age: Duration,
+        now: DateTime<Utc>,
+        is_stale: bool,
+    ) -> UsageCache {
+        let Some(profiles) = self.tools.get(tool.id()) else {
+            return UsageCache::new();
+        };
+        profiles
+            .iter()
+            .filter(|(_, e)| is_within(e.fetched_at, max_age, now))
+            .map(|(profile, e)| {
+                let entry = ProfileUsageCache {
+                    usage_lines: e
+                        .windows
+                        .iter()
+                        .map(|w| UsageLine::Data {
+                            label: w.label.clone(),
+                            percent: w.percent,
+                            resets_at: w.resets_at,
+                        })
+                        .collect(),
+                    plan_type: e.plan_type.clone(),
+                    is_stale,
+                    rate_limited_until: None,
+                };
+                (profile.clone(), entry)
+            })
+            .collect()
+    }
+
+    /// Replaces the entries of freshly fetched profiles; stale or data-less
+    /// entries keep their previous value.
+    fn update(&mut self, tool: Tool, cache: &UsageCache, now: DateTime<Utc>) -> bool {
+        let mut changed = false;
+        let profiles = self.tools.entry(tool.id().to_string()).or_default();
+        for (profile, entry) in cache.iter().filter(|(_, e)| !e.is_stale) {
+            let windows: Vec<StoredWindow> = entry
+                .usage_lines
+                .iter()
+                .filter_map(|line| match line {
+                    UsageLine::Data {
+                        label,
+                        percent,
+                        resets_at,
+                    } => Some(StoredWindow {
+                        label: label.clone(),
+                        percent: *percent,
+                        resets_at: *resets_at,
+                    }),
+                    UsageLine::Text(_) => None,
+                })
+                .collect();
+            if windows.is_empty() {
+                continue;
+            }
+            profiles.insert(
+                profile.clone(),
+                StoredEntry {
+                    fetched_at: now,
+                    plan_type: entry.plan_type.clone(),
+                    windows,
+                },
+            );
+            changed = true;
+        }
+        changed
+    }
+}
+
+/// Stores the fresh entries of a just-fetched cache on disk. Concurrent
+/// dashboards and `aip watch` runs serialize on `usage-cache.json.lock`.
+pub fn record(tool: Tool, cache: &UsageCache) -> Result<()> {
+    let path = cache_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let _lock = FileLock::acquire(&path.with_extension("json.lock"), LOCK_TIMEOUT)?;
+    let mut stored = StoredUsage::load_from(&path);
+    if stored.update(tool, cache, Utc::now()) {
+        stored.save_to(&path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage::ProfileUsageCache;
+
+    fn entry(usage_lines: Vec<UsageLine>, is_stale: bool) -> ProfileUsageCache {
+        ProfileUsageCache {
+            usage_lines,
+            plan_type: Some("max".to_string()),
+            is_stale,
+            rate_limited_until: None,
+        }
+    }
+
+    fn data(percent: f64) -> UsageLine {
+        UsageLine::Data {
+            label: "5-hour".to_string(),
+            percent,
+            resets_at: None,
+        }
+    }
+
+    #[test]
+    fn update_keeps_previous_entry_for_stale_or_empty_fetches() {
+        let first: DateTime<Utc> = "2026-01-01T00:00:00Z".parse().unwrap();
+        let later: DateTime<Utc> = "2026-01-01T01:00:00Z".parse().unwrap();
+        let mut stored = StoredUsage::default();
+
+        stored.update(
+            Tool::Claude,
+            &UsageCache::from([
+                ("a".to_string(), entry(vec![data(10.0)], false)),
+                ("b".to_string(), entry(vec![data(20.0)], false)),
+            ]),
+            first,
+        );
+        let changed = stored.update(
+            Tool::Claude,
+            &UsageCache::from([
+                ("a".to_string(), entry(vec![data(99.0)], true)),
+                (
+                    "b".to_string(),
+                    entry(vec![UsageLine::Text("Rate limited".to_string())], false),
+                ),
+            ]),
+            later,
+        );
+
+        assert!(!changed);
+        let a = stored.entry(Tool::Claude, "a").unwrap();
+        assert_eq!(a.fetched_at, first);
+        assert_eq!(a.windows[0].percent, 10.0);
+        assert_eq!(
+            stored.entry(Tool::Claude, "b").unwrap().windows[0].percent,
+            20.0
+        );
+        assert!(stored.entry(Tool::Codex, "a").is_none());
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("aip").join("usage-cache.json");
+        let mut stored = StoredUsage::default();
+        stored.update(
+            Tool::Codex,
+            &UsageCache::from([("dev".to_string(), entry(vec![data(33.0)], false))]),
+            Utc::now(),
+        );
+
+        stored.save_to(&path).unwrap();
+
+        assert_eq!(StoredUsage::load_from(&path), stored);
+    }
+
+    #[test]
+    fn usage_cache_skips_entries_older_than_max_age() {
+        let now: DateTime<Utc> = "2026-01-02T00:00:00Z".parse().unwrap();
+        let mut stored = StoredUsage::default();
+        stored.update(
+            Tool::Claude,
+            &UsageCache::from([("old".to_string(), entry(vec![data(10.0)], false))]),
+            "2026-01-01T00:00:00Z".parse().unwrap(),
+        );
+        stored.update(
+            Tool::Claude,
+            &UsageCache::from([("new".to_string(), entry(vec![data(20.0)], false))]),
+            "2026-01-01T23:00:00Z".parse().unwrap(),
+        );
+        let max_age = Duration::from_secs(2 * 3600);
+
+        let cache = stored.usage_cache(Tool::Claude, max_age, now, true);
+
+        assert_eq!(cache.len(), 1);
+        let new = &cache["new"];
+        assert!(new.is_stale);
+        assert_eq!(new.plan_type.as_deref(), Some("max"));
+        assert_eq!(new.usage_lines, vec![data(20.0)]);
+        assert!(
+            stored
+                .fresh_entry(Tool::Claude, "old", max_age, now)
+                .is_none()
+        );
+        assert!(
+            stored
+                .fresh_entry(Tool::Claude, "new", max_age, now)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn load_from_missing_file_is_empty() {
+        let dir = tempfile::tempdir().unwrap();
+
+        assert_eq!(
+            StoredUsage::load_from(&dir.path().join("usage-cache.json")),
+            StoredUsage::default()
+        );
+    }
+}
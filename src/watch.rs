@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use chrono::{DateTime, Local, Utc};
+use serde::Serialize;
+
+use crate::auto_switch;
+use crate::config::Config;
+use crate::notify::{self, Notifier};
+use crate::tool::Tool;
+use crate::usage::{
+    UsageCache, UsageReport, build_reports, format_oneline, merge_usage_cache,
+    prefetch_claude_usage, prefetch_codex_usage, rate_limited_until,
+};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+const DEFAULT_THRESHOLD: f64 = 90.0;
+
+/// Something `aip watch` observed, printed as text or one JSON line.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+    Usage(UsageReport),
+    ThresholdCrossed {
+        tool: &'static str,
+        profile: String,
+        window: String,
+        used_percent: f64,
+        threshold: f64,
+    },
+    RateLimited {
+        tool: &'static str,
+        profile: String,
+        until: DateTime<Utc>,
+    },
+    CredentialsChanged {
+        tool: &'static str,
+        profile: Option<String>,
+        reason: &'static str,
+    },
+    AutoSwitched {
+        tool: &'static str,
+        from: String,
+        to: String,
+        reason: String,
+    },
+    Error {
+        tool: &'static str,
+        message: String,
+    },
+}
+
+#[derive(Serialize)]
+struct Envelope<'a> {
+    at: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+impl Event {
+    fn text(&self) -> String {
+        match self {
+            Event::Usage(report) => format_oneline(report),
+            Event::ThresholdCrossed {
+                tool,
+                profile,
+                window,
+                used_percent,
+                threshold,
+            } => format!(
+                "{}/{}: {} usage at {:.1}% crossed {}%",
+                tool, profile, window, used_percent, threshold
+            ),
+            Event::RateLimited {
+                tool,
+                profile,
+                until,
+            } => format!(
+                "{}/{}: rate limited until {}",
+                tool,
+                profile,
+                until.with_timezone(&Local).format("%H:%M:%S")
+            ),
+            Event::CredentialsChanged {
+                tool,
+                profile,
+                reason,
+            } => format!(
+                "{}/{}: credentials changed ({})",
+                tool,
+                profile.as_deref().unwrap_or("-"),
+                reason
+            ),
+            Event::AutoSwitched {
+                tool,
+                from,
+                to,
+                reason,
+            } => format!("{}: switched from '{}' to '{}': {}", tool, from, to, reason),
+            Event::Error { tool, message } => format!("{}: {}", tool, message),
+        }
+    }
+
+    fn emit(&self, json: bool) {
+        let at = Utc::now();
+        if json {
+            if let Ok(line) = serde_json::to_string(&Envelope { at, event: self }) {
+                println!("{}", line);
+            }
+        } else {
+            println!(
+                "{} {}",
+                at.with_timezone(&Local).format("%H:%M:%S"),
+                self.text()
+            );
+        }
+    }
+}
+
+/// Windows whose used percentage reached `threshold` since the last fetch.
+///
+/// `last_seen` holds the previous fresh reading per window; stale reports are ignored.
+fn threshold_events(
+    reports: &[UsageReport],
+    threshold: f64,
+    last_seen: &mut HashMap<(Tool, String, String), f64>,
+) -> Vec<Event> {
+    let mut events = Vec::new();
+    for report in reports.iter().filter(|r| !r.stale) {
+        for window in &report.windows {
+            let key = (report.tool, report.profile.clone(), window.label.clone());
+            let previous = last_seen.insert(key, window.used_percent);
+            if window.used_percent >= threshold && previous.is_none_or(|p| p < threshold) {
+                events.push(Event::ThresholdCrossed {
+                    tool: report.tool_id,
+                    profile: report.profile.clone(),
+                    window: window.label.clone(),
+                    used_percent: window.used_percent,
+                    threshold,
+                });
+            }
+        }
+    }
+    events
+}
+
+fn rate_limit_events(tool: Tool, cache: &UsageCache, now: DateTime<Utc>) -> Vec<Event> {
+    let mut limited: Vec<(&String, DateTime<Utc>)> = cache
+        .iter()
+        .filter_map(|(profile, entry)| {
+            let until = entry.rate_limited_until.filter(|until| *until > now)?;
+            Some((profile, until))
+        })
+        .collect();
+    limited.sort();
+    limited
+        .into_iter()
+        .map(|(profile, until)| Event::RateLimited {
+            tool: tool.id(),
+            profile: profile.clone(),
+            until,
+        })
+        .collect()
+}
+
+/// The current profile and a fingerprint of its stored credentials.
+#[derive(Clone, Debug, PartialEq)]
+struct CredentialState {
+    profile: Option<String>,
+    fingerprint: Option<u64>,
+}
+
+fn credential_state(tool: Tool) -> CredentialState {
+    let profile = tool.current_profile().ok().flatten();
+    let fingerprint = profile
+        .as_deref()
+        .and_then(|p| tool.profile_dir(p).ok())
+        .and_then(|dir| std::fs::read(dir.join(tool.credential_file())).ok())
+        .map(|content| {
+            let mut hasher = DefaultHasher::new();
+            content.hash(&mut hasher);
+            hasher.finish()
+        });
+    CredentialState {
+        profile,
+        fingerprint,
+    }
+}
+
+fn credential_event(
+    tool: Tool,
+    previous: Option<&CredentialState>,
+    state: &CredentialState,
+) -> Option<Event> {
+    let previous = previous?;
+    let reason = if previous.profile != state.profile {
+        "current profile changed"
+    } else if previous.fingerprint != state.fingerprint {
+        "credentials updated"
+    } else {
+        return None;
+    };
+    Some(Event::CredentialsChanged {
+        tool: tool.id(),
+        profile: state.profile.clone(),
+        reason,
+    })
+}
+
+pub async fn cmd_watch(interval_secs: Option<u64>, json: bool) -> Result<()> {
+    let config = Config::load();
+    let policy = config.auto_switch.clone();
+    let threshold = policy.as_ref().map_or(DEFAULT_THRESHOLD, |p| p.threshold);
+    let interval = match interval_secs {
+        Some(0) => bail!("--interval must be greater than zero"),
+        Some(secs) => Duration::from_secs(secs),
+        None => config.refresh_interval().unwrap_or(DEFAULT_INTERVAL),
+    };
+
+    if !json {
+        let auto_switch = match &policy {
+            Some(p) => format!("auto-switching at {}%", p.threshold),
+            None => "auto-switch off".to_string(),
+        };
+        println!(
+            "Watching usage every {}s, {}",
+            interval.as_secs(),
+            auto_switch
+        );
+    }
+
+    let mut notifier = Notifier::from_config(&config);
+    let mut usage_caches: HashMap<Tool, UsageCache> = HashMap::new();
+    let mut last_seen = HashMap::new();
+    let mut credentials: HashMap<Tool, CredentialState> = HashMap::new();
+    let mut ticker = tokio::time::interval(interval);
+    let ctrl_c_future = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c_future);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = &mut ctrl_c_future => return Ok(()),
+        }
+
+        for tool in Tool::ALL {
+            // Wait out an active retry-after window instead of fetching again.
+            if rate_limited_until(usage_caches.get(&tool), Utc::now()).is_some() {
+                continue;
+            }
+            let profiles = tool.list_profiles().unwrap_or_default();
+            if profiles.is_empty() {
+                continue;
+            }
+
+            let cache = match tool {
+                Tool::Claude => prefetch_claude_usage(&profiles).await,
+                Tool::Codex => prefetch_codex_usage(&profiles).await,
+            };
+            if let Some(notifier) = &mut notifier {
+                notify::spawn_hooks(notifier.evaluate(tool, &cache, Utc::now()));
+            }
+            let merged = merge_usage_cache(cache, usage_caches.get(&tool));
+
+            // Fetching syncs the active credentials, so compare afterwards.
+            let state = credential_state(tool);
+            let mut events: Vec<Event> = credential_event(tool, credentials.get(&tool), &state)
+                .into_iter()
+                .collect();
+            credentials.insert(tool, state.clone());
+
+            let reports = build_reports(tool, &profiles, state.profile.as_deref(), &merged);
+            events.extend(threshold_events(&reports, threshold, &mut last_seen));
+            events.extend(rate_limit_events(tool, &merged, Utc::now()));
+            let usage_events: Vec<Event> = reports.into_iter().map(Event::Usage).collect();
+
+            for event in usage_events.iter().chain(&events) {
+                event.emit(json);
+            }
+
+            if let Some(policy) = &policy {
+                let event = match auto_switch::run(policy, tool, &merged).await {
+                    Ok(Some(decision)) => Some(Event::AutoSwitched {
+                        tool: tool.id(),
+                        reason: decision.reason(),
+                        from: decision.from,
+                        to: decision.to,
+                    }),
+                    Ok(None) => None,
+                    Err(e) => Some(Event::Error {
+                        tool: tool.id(),
+                        message: format!("auto-switch failed: {}", e),
+                    }),
+                };
+                if let Some(event) = event {
+                    event.emit(json);
+                    credentials.insert(tool, credential_state(tool));
+                }
+            }
+            usage_caches.insert(tool, merged);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::usage::WindowReport;
+
+    fn report(profile: &str, percent: f64, stale: bool) -> UsageReport {
+        UsageReport {
+            tool: Tool::Claude,
+            tool_id: "claude",
+            profile: profile.to_string(),
+            current: false,
+            plan_type: None,
+            stale,
+            fetched_at: None,
+            windows: vec![WindowReport {
+                label: "5-hour".to_string(),
+                used_percent: percent,
+                resets_at: None,
+            }],
+            message: None,
+        }
+    }
+
+    #[test]
+    fn threshold_events_fire_once_per_crossing() {
+        let mut last_seen = HashMap::new();
+
+        let first = threshold_events(&[report("a", 95.0, false)], 90.0, &mut last_seen);
+        let repeat = threshold_events(&[report("a", 96.0, false)], 90.0, &mut last_seen);
+        let _ = threshold_events(&[report("a", 10.0, false)], 90.0, &mut last_seen);
+        let again = threshold_events(&[report("a", 91.0, false)], 90.0, &mut last_seen);
+
+        assert_eq!(first.len(), 1);
+        assert!(repeat.is_empty());
+        assert_eq!(again.len(), 1);
+    }
+
+    #[test]
+    fn threshold_events_ignore_stale_reports() {
+        let mut last_seen = HashMap::new();
+
+        let events = threshold_events(&[report("a", 99.0, true)], 90.0, &mut last_seen);
+
+        assert!(events.is_empty());
+        assert!(last_seen.is_empty());
+    }
+
+    #[test]
+    fn credential_event_detects_profile_and_content_changes() {
+        let state = |profile: &str, fingerprint| CredentialState {
+            profile: Some(profile.to_string()),
+            fingerprint: Some(fingerprint),
+        };
+
+        assert!(credential_event(Tool::Codex, None, &state("a", 1)).is_none());
+        assert!(credential_event(Tool::Codex, Some(&state("a", 1)), &state("a", 1)).is_none());
+        assert!(matches!(
+            credential_event(Tool::Codex, Some(&state("a", 1)), &state("b", 1)),
+            Some(Event::CredentialsChanged {
+                reason: "current profile changed",
+                ..
+            })
+        ));
+        assert!(matches!(
+            credential_event(Tool::Codex, Some(&state("a", 1)), &state("a", 2)),
+            Some(Event::CredentialsChanged {
+                reason: "credentials updated",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn events_serialize_as_tagged_json_lines() {
+        let at: DateTime<Utc> = "2026-01-01T00:00:00Z".parse().unwrap();
+        let event = Event::RateLimited {
+            tool: "claude",
+            profile: "work".to_string(),
+            until: at,
+        };
+
+        let value = serde_json::to_value(Envelope { at, event: &event }).unwrap();
+
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "at": "2026-01-01T00:00:00Z",
+                "event": "rate_limited",
+                "tool": "claude",
+                "profile": "work",
+                "until": "2026-01-01T00:00:00Z"
+            })
+        );
+
+        let usage = serde_json::to_value(Envelope {
+            at,
+            event: &Event::Usage(report("a", 12.5, false)),
+        })
+        .unwrap();
+        assert_eq!(usage["event"], "usage");
+        assert_eq!(usage["tool"], "claude");
+        assert_eq!(usage["windows"][0]["used_percent"], 12.5);
+    }
+}